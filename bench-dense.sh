@@ -0,0 +1,8 @@
+#!/bin/bash
+# Times a dense size, where most of the work is passing results to the
+# printer thread. Usage: ./bench-dense.sh WORDLIST [compute args...]
+set -e
+cargo build --release --no-default-features --features="width-3,height-3,square" --target-dir target/bench-3x3
+for run in 1 2 3; do
+    TIMEFORMAT="run $run: %R s"; time target/bench-3x3/release/rust-word-square compute -q "$@" > /dev/null
+done
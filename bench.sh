@@ -0,0 +1,8 @@
+#!/bin/bash
+# Times index building and full searches of the reference sizes on generated
+# words, for comparing compute's speed between commits. Extra args go to the
+# bench subcommand, eg a wordlist, --sizes or --format csv. Built with the
+# sweep feature so one binary can run every size.
+set -e
+cargo build --release --features sweep --target-dir target/sweep
+target/sweep/release/rust-word-square bench "$@"
@@ -0,0 +1,120 @@
+//! Symbol frequency analysis behind `analyze-alphabet`, for choosing which
+//! characters get a code and in what order.
+//!
+//! Spaces aren't counted, since with the phrases feature the space between
+//! phrase words always takes the code after the letters.
+
+use std::collections::BTreeMap;
+use std::io;
+use std::io::prelude::*;
+
+use fnv::FnvHashMap;
+use serde_json::Value;
+
+use super::{alphabet, encode};
+
+/// Most letters an alphabet can have, as builds without the phrases feature
+/// keep a bit per letter in a u32 CharSet.
+const MAX_LETTER_CODES:usize = 32;
+
+#[derive(Debug,Default)]
+pub struct AlphabetCounts {
+    words: u64,
+    total: FnvHashMap<char,u64>,
+    by_length: BTreeMap<usize,FnvHashMap<char,u64>>,
+    /// Number of words using each distinct set of chars, for working out how
+    /// many words a given alphabet would lose.
+    char_sets: FnvHashMap<Vec<char>,u64>,
+}
+
+impl AlphabetCounts {
+    pub fn add_word(&mut self, word:&str) {
+        let chars:Vec<char> = word.chars().filter(|&c| c != ' ').collect();
+        let per_length = self.by_length.entry(chars.len()).or_default();
+        for &c in &chars {
+            *self.total.entry(c).or_insert(0) += 1;
+            *per_length.entry(c).or_insert(0) += 1;
+        }
+        let mut set = chars;
+        set.sort_unstable();
+        set.dedup();
+        *self.char_sets.entry(set).or_insert(0) += 1;
+        self.words += 1;
+    }
+
+    /// Every char seen, most frequent first.
+    pub fn ordered(&self) -> Vec<(char,u64)> {
+        let mut counts:Vec<(char,u64)> = self.total.iter().map(|(&c, &n)| (c, n)).collect();
+        counts.sort_unstable_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(&b.0)));
+        counts
+    }
+
+    /// Number of words containing at least one char not in `alphabet`.
+    fn words_lost(&self, alphabet:&[char]) -> u64 {
+        self.char_sets.iter()
+            .filter(|&(set, _)| set.iter().any(|c| !alphabet.contains(c)))
+            .map(|(_, &n)| n)
+            .sum()
+    }
+
+    /// Writes the alphabet as a `make_encode_decode!` invocation and the
+    /// `LETTER_CODES` to go with it, followed by comments listing what didn't
+    /// fit. Both need replacing together, as the space and `alphabet()` rely
+    /// on `LETTER_CODES` being the number of letters.
+    pub fn write_macro(&self, out:&mut impl Write) -> io::Result<()> {
+        let ordered = self.ordered();
+        let budget = MAX_LETTER_CODES;
+        let fitting:Vec<char> = ordered.iter().take(budget).map(|&(c, _)| c).collect();
+        writeln!(out, "// Ordered by frequency over {} words; `new` marks chars the current alphabet can't encode.", self.words)?;
+        writeln!(out, "make_encode_decode!{{")?;
+        for (code, &(c, count)) in ordered.iter().take(budget).enumerate() {
+            let new = if encode(c).is_none() { " new," } else { "" };
+            writeln!(out, "    {} => {:?}; //{} {} uses", code, c, new, count)?;
+        }
+        writeln!(out, "    #[cfg(feature = \"phrases\")]")?;
+        writeln!(out, "    {} => ' '; // space between phrase words", fitting.len())?;
+        writeln!(out, "}}")?;
+        writeln!(out)?;
+        writeln!(out, "/// Number of codes for letters in the alphabet above.")?;
+        writeln!(out, "const LETTER_CODES:u8 = {};", fitting.len())?;
+        if ordered.len() > budget {
+            writeln!(out, "// {} chars don't fit in {} codes, losing {} words:", ordered.len() - budget, budget, self.words_lost(&fitting))?;
+            for &(c, count) in &ordered[budget..] {
+                writeln!(out, "//     {:?} (U+{:04X}) {} uses", c, c as u32, count)?;
+            }
+        }
+        let dropped:Vec<char> = alphabet().into_iter().filter(|&c| c != ' ' && !fitting.contains(&c)).collect();
+        if !dropped.is_empty() {
+            writeln!(out, "// Current chars left out: {:?}", dropped)?;
+        }
+        Ok(())
+    }
+
+    pub fn to_json(&self) -> Value {
+        let ordered = self.ordered();
+        let budget = MAX_LETTER_CODES;
+        let fitting:Vec<char> = ordered.iter().take(budget).map(|&(c, _)| c).collect();
+        let entry = |(c, count):(char, u64)| json!({
+            "char": c.to_string(),
+            "codepoint": format!("U+{:04X}", c as u32),
+            "count": count,
+            "encodable": encode(c).is_some(),
+        });
+        let by_length:Vec<Value> = self.by_length.iter().map(|(len, counts)| {
+            let mut counts:Vec<(char,u64)> = counts.iter().map(|(&c, &n)| (c, n)).collect();
+            counts.sort_unstable_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(&b.0)));
+            json!({
+                "length": len,
+                "chars": counts.into_iter().map(&entry).collect::<Vec<Value>>(),
+            })
+        }).collect();
+        json!({
+            "words": self.words,
+            "budget": budget,
+            "alphabet": ordered.iter().take(budget).cloned().map(&entry).collect::<Vec<Value>>(),
+            "overflow": ordered.iter().skip(budget).cloned().map(&entry).collect::<Vec<Value>>(),
+            "words_lost": self.words_lost(&fitting),
+            "by_length": by_length,
+        })
+    }
+}
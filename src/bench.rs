@@ -0,0 +1,185 @@
+//! `bench`: times index building and full searches for several sizes, so
+//! changes to `compute` or `WordIndex` can be checked for regressions.
+//!
+//! Without a wordlist, words are generated from English letter frequencies
+//! with a fixed seed, so runs on different commits search the same words.
+//!
+//! This is the benchmark harness rather than a `benches/` one using criterion:
+//! the crate is a single binary, so a `cargo bench` target couldn't reach
+//! `WordIndex` or `compute` without splitting them out into a library. Run it
+//! with `bench.sh`.
+
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use clap::Arg;
+use fnv::FnvHashSet;
+use serde_json::Value;
+
+use super::{encode, scheduler, EncodedWord, LoadedWords, SPLIT_TASKS_PER_THREAD};
+use super::rng::Rng;
+use super::search_stats::NoStats;
+
+/// Sizes run by default in builds with the sweep feature, small enough to
+/// search in seconds on generated words.
+pub const REFERENCE_SIZES:&str = "3x3,4x4,5x4,5x5,6x5";
+
+/// Arguments of the bench subcommand besides the shared wordlist ones.
+pub fn bench_args<'a, 'b>() -> Vec<Arg<'a, 'b>> {
+    vec![
+        Arg::with_name("generated-words")
+            .long("generated-words")
+            .takes_value(true)
+            .default_value("800")
+            .validator(|arg| {
+                match arg.parse::<usize>() {
+                    Ok(_) => Ok(()),
+                    Err(e) => Err(format!("Must provide a valid integer. {:?}", e)),
+                }
+            })
+            .help("Without a wordlist, how many words of each needed length to generate"),
+        Arg::with_name("seed")
+            .long("seed")
+            .takes_value(true)
+            .default_value("1")
+            .validator(|arg| {
+                match arg.parse::<u64>() {
+                    Ok(_) => Ok(()),
+                    Err(e) => Err(format!("Must provide a valid integer. {:?}", e)),
+                }
+            })
+            .help("Seed for generating words"),
+        Arg::with_name("runs")
+            .long("runs")
+            .takes_value(true)
+            .default_value("3")
+            .validator(|arg| {
+                match arg.parse::<usize>() {
+                    Ok(n) if n >= 1 => Ok(()),
+                    Ok(_) => Err(String::from("Must be at least 1")),
+                    Err(e) => Err(format!("Must provide a valid integer. {:?}", e)),
+                }
+            })
+            .help("How many times to run each size; the fastest run is reported"),
+    ]
+}
+
+/// Letters and their rough frequency in English text, per 1000 letters.
+const LETTER_WEIGHTS:&[(char, u32)] = &[
+    ('e', 127), ('t', 91), ('a', 82), ('o', 75), ('i', 70), ('n', 67), ('s', 63),
+    ('h', 61), ('r', 60), ('d', 43), ('l', 40), ('c', 28), ('u', 28), ('m', 24),
+    ('w', 24), ('f', 22), ('g', 20), ('y', 20), ('p', 19), ('b', 15), ('v', 10),
+    ('k', 8), ('j', 2), ('x', 2), ('q', 1), ('z', 1),
+];
+
+fn random_letter(rng:&mut Rng) -> char {
+    let total:u32 = LETTER_WEIGHTS.iter().map(|&(_, w)| w).sum();
+    let mut pick = rng.below(total as u64) as u32;
+    for &(c, weight) in LETTER_WEIGHTS {
+        if pick < weight {
+            return c;
+        }
+        pick -= weight;
+    }
+    unreachable!()
+}
+
+/// Makes `count` distinct random words of each of `lengths`.
+pub fn generate_words(lengths:&[usize], count:usize, seed:u64) -> LoadedWords {
+    let mut rng = Rng::new(seed);
+    let mut lengths = lengths.to_vec();
+    lengths.sort();
+    lengths.dedup();
+    let mut rows = Vec::new();
+    for len in lengths {
+        let mut seen = FnvHashSet::default();
+        // Short lengths may not have `count` distinct words.
+        let mut attempts = 0;
+        while seen.len() < count && attempts < count * 10 {
+            attempts += 1;
+            let codes:Vec<u8> = (0..len).map(|_| encode(random_letter(&mut rng)).unwrap()).collect();
+            if seen.insert(codes.clone()) {
+                rows.push(EncodedWord{codes, meta: Vec::new()});
+            }
+        }
+    }
+    LoadedWords{meta_columns: Vec::new(), rows, cols: None, max_memory: None}
+}
+
+/// The fastest run of one size.
+#[derive(Debug,Clone,Default)]
+pub struct BenchResult {
+    pub width: usize,
+    pub height: usize,
+    pub row_words: u32,
+    pub col_words: u32,
+    pub index_time: Duration,
+    pub search_time: Duration,
+    pub nodes: u64,
+    pub squares: u64,
+}
+
+pub const COLUMNS:&[&str] = &[
+    "width", "height", "row_words", "col_words", "index_seconds", "search_seconds",
+    "nodes", "nodes_per_second", "squares", "squares_per_second",
+];
+
+impl BenchResult {
+    pub fn row(&self) -> Vec<Value> {
+        let secs = self.search_time.as_secs_f64().max(1e-9);
+        vec![
+            json!(self.width),
+            json!(self.height),
+            json!(self.row_words),
+            json!(self.col_words),
+            json!(self.index_time.as_secs_f64()),
+            json!(self.search_time.as_secs_f64()),
+            json!(self.nodes),
+            json!((self.nodes as f64 / secs).round() as u64),
+            json!(self.squares),
+            json!((self.squares as f64 / secs).round() as u64),
+        ]
+    }
+}
+
+/// Indexes `words` for a `W` by `H` grid and searches it `runs` times on
+/// `num_threads` threads, keeping the fastest index build and search.
+/// Squares are only counted, not written.
+pub fn bench_size<const W:usize, const H:usize, const S:usize>(
+    words:&LoadedWords,
+    num_threads:usize,
+    runs:usize,
+) -> BenchResult {
+    let mut result = BenchResult{width: W, height: H, ..Default::default()};
+    for run in 0..runs {
+        let start = Instant::now();
+        let (row_words, col_words, index) = words.index::<W, H>();
+        let index_time = start.elapsed();
+        result.row_words = row_words;
+        result.col_words = col_words;
+        if index.is_empty() {
+            break;
+        }
+
+        let index = Arc::new(index);
+        let squares = Arc::new(AtomicU64::new(0));
+        let counter = Arc::clone(&squares);
+        let start = Instant::now();
+        let (roots, split_nodes) = scheduler::split_adaptive(&index, ([255u8; S], 0), num_threads * SPLIT_TASKS_PER_THREAD, &mut NoStats);
+        let (stats, NoStats) = scheduler::run(index, roots, num_threads, move |batch| {
+            counter.fetch_add(batch.len() as u64, Ordering::Relaxed);
+        });
+        let search_time = start.elapsed();
+
+        result.nodes = split_nodes + stats.iter().map(|s| s.nodes).sum::<u64>();
+        result.squares = squares.load(Ordering::SeqCst);
+        if run == 0 || index_time < result.index_time {
+            result.index_time = index_time;
+        }
+        if run == 0 || search_time < result.search_time {
+            result.search_time = search_time;
+        }
+    }
+    result
+}
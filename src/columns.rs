@@ -0,0 +1,140 @@
+//! Tab-separated wordlists with metadata columns, eg word, frequency and part
+//! of speech, described with `--columns word,freq,pos`.
+//!
+//! The word column is what gets encoded; the other columns are kept alongside
+//! each indexed word so filters and output can use them. A column named "-" is
+//! read past and dropped.
+
+use std::io;
+
+use clap::{Arg, ArgMatches};
+use fnv::FnvHashMap;
+use serde_json::{Map, Value};
+
+use super::invalid_data;
+
+/// Arguments read by `Schema::from_args`.
+pub fn column_args<'a, 'b>() -> Vec<Arg<'a, 'b>> {
+    vec![
+        Arg::with_name("columns")
+            .long("columns")
+            .takes_value(true)
+            .help("Read the wordlist as tab-separated columns with these names, eg \"word,freq,pos\". One must be \"word\"; \"freq\" and \"pos\" are used by --min-freq and --pos, and \"-\" skips a column."),
+    ]
+}
+
+#[derive(Debug,Clone,PartialEq,Eq)]
+pub struct Schema {
+    names: Vec<String>,
+    word: usize,
+}
+
+impl Schema {
+    pub fn parse(spec:&str) -> io::Result<Schema> {
+        let names:Vec<String> = spec.split(',').map(|n| n.trim().to_string()).collect();
+        for (i, name) in names.iter().enumerate() {
+            if name.is_empty() {
+                return Err(invalid_data("--columns has an empty column name"));
+            }
+            if name != "-" && names[..i].contains(name) {
+                return Err(invalid_data(&format!("--columns names {:?} twice", name)));
+            }
+        }
+        match names.iter().position(|n| n == "word") {
+            Some(word) => Ok(Schema{names, word}),
+            None => Err(invalid_data("--columns must include a \"word\" column")),
+        }
+    }
+
+    pub fn from_args(args:&ArgMatches) -> io::Result<Option<Schema>> {
+        args.value_of("columns").map(Schema::parse).transpose()
+    }
+
+    /// Names of the kept metadata columns, in input order.
+    pub fn meta_columns(&self) -> Vec<String> {
+        self.names.iter().enumerate()
+            .filter(|&(i, name)| i != self.word && name != "-")
+            .map(|(_, name)| name.clone())
+            .collect()
+    }
+
+    /// Position of the named metadata column among `meta_columns`.
+    pub fn meta_index(&self, name:&str) -> Option<usize> {
+        self.meta_columns().iter().position(|n| n == name)
+    }
+
+    /// Splits a line into its word and metadata values. Missing columns are
+    /// read as empty and extra ones are ignored.
+    pub fn split(&self, line:&str) -> (String, Vec<String>) {
+        let mut fields = line.split('\t');
+        let mut word = String::new();
+        let mut values = Vec::new();
+        for (i, name) in self.names.iter().enumerate() {
+            let field = fields.next().unwrap_or("");
+            if i == self.word {
+                word = field.to_string();
+            } else if name != "-" {
+                values.push(field.to_string());
+            }
+        }
+        (word, values)
+    }
+
+    /// Puts a word and its metadata back into a line with the kept columns.
+    pub fn join(&self, word:&str, values:&[String]) -> String {
+        let mut values = values.iter();
+        let fields:Vec<&str> = self.names.iter().enumerate()
+            .filter(|&(_, name)| name != "-")
+            .map(|(i, _)| if i == self.word { word } else { values.next().map(|v| v.as_str()).unwrap_or("") })
+            .collect();
+        fields.join("\t")
+    }
+}
+
+/// Metadata of indexed words, keyed by the word's codes. Only the first
+/// occurrence of a word keeps its metadata.
+#[derive(Debug,Default)]
+pub struct WordMetadata {
+    pub columns: Vec<String>,
+    words: FnvHashMap<Vec<u8>,Vec<String>>,
+}
+
+impl WordMetadata {
+    pub fn new(columns:Vec<String>) -> WordMetadata {
+        WordMetadata{columns, words: FnvHashMap::default()}
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.columns.is_empty()
+    }
+
+    pub fn len(&self) -> usize {
+        self.words.len()
+    }
+
+    pub fn insert(&mut self, codes:&[u8], values:Vec<String>) {
+        self.words.entry(codes.to_vec()).or_insert(values);
+    }
+
+    pub fn get(&self, codes:&[u8]) -> Option<&Vec<String>> {
+        self.words.get(codes)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&Vec<u8>, &Vec<String>)> {
+        self.words.iter()
+    }
+
+    /// The metadata of a word as a JSON object, or null if it has none.
+    pub fn to_json(&self, codes:&[u8]) -> Value {
+        match self.get(codes) {
+            Some(values) => {
+                let mut obj = Map::new();
+                for (name, value) in self.columns.iter().zip(values) {
+                    obj.insert(name.clone(), Value::String(value.clone()));
+                }
+                Value::Object(obj)
+            },
+            None => Value::Null,
+        }
+    }
+}
@@ -0,0 +1,107 @@
+//! Generates compound words for `wordlist-combine`: tuples of words joined by
+//! a separator, restricted to total lengths a grid can use.
+//!
+//! Words are bucketed by length, so for each target length only the length
+//! combinations that add up to it are visited, and tuples are written out as
+//! they are generated rather than collected first.
+
+use std::collections::BTreeMap;
+use std::io;
+
+use fnv::FnvHashSet;
+
+use super::wordlist_writer::WordlistWriter;
+
+#[derive(Debug,Clone)]
+pub struct CombineOptions {
+    /// Number of words in each compound.
+    pub words: usize,
+    pub separator: String,
+    /// Total lengths (in chars, including separators) to generate.
+    pub lengths: Vec<usize>,
+    /// Only generate each multiset of words once, shortest words first, rather
+    /// than every ordering of it.
+    pub unordered: bool,
+}
+
+pub struct Combiner {
+    /// Distinct words of each length, in order of first appearance.
+    by_length: BTreeMap<usize,Vec<String>>,
+}
+
+impl Combiner {
+    pub fn new(words:impl IntoIterator<Item = String>) -> Combiner {
+        let mut by_length:BTreeMap<usize,Vec<String>> = BTreeMap::new();
+        let mut seen = FnvHashSet::default();
+        for word in words {
+            if !word.is_empty() && seen.insert(word.clone()) {
+                by_length.entry(word.chars().count()).or_default().push(word);
+            }
+        }
+        Combiner{by_length}
+    }
+
+    /// Writes every compound, returning how many were written.
+    pub fn write_all(&self, opts:&CombineOptions, out:&mut WordlistWriter) -> io::Result<u64> {
+        let sep_len = opts.separator.chars().count();
+        let mut count = 0;
+        let mut lengths = opts.lengths.clone();
+        lengths.sort_unstable();
+        lengths.dedup();
+        for total in lengths {
+            let separators = sep_len * (opts.words.saturating_sub(1));
+            if total < separators {
+                continue;
+            }
+            let mut buf = String::new();
+            count += self.write_tuples(opts, out, total - separators, opts.words, None, &mut buf)?;
+        }
+        Ok(count)
+    }
+
+    /// Appends `remaining_words` more words with `remaining_len` letters between
+    /// them to `buf`. `after` is the (length, index) of the previous word, which
+    /// unordered tuples must not go below.
+    fn write_tuples(
+        &self,
+        opts:&CombineOptions,
+        out:&mut WordlistWriter,
+        remaining_len:usize,
+        remaining_words:usize,
+        after:Option<(usize,usize)>,
+        buf:&mut String,
+    ) -> io::Result<u64> {
+        if remaining_words == 0 {
+            if remaining_len != 0 {
+                return Ok(0);
+            }
+            out.write(buf)?;
+            return Ok(1);
+        }
+        let mut count = 0;
+        let prefix_len = buf.len();
+        for (&len, words) in self.by_length.range(..=remaining_len) {
+            // The words still to come need at least one letter each.
+            let min_rest = self.by_length.keys().next().cloned().unwrap_or(1) * (remaining_words - 1);
+            if len + min_rest > remaining_len {
+                break;
+            }
+            let start = match after {
+                Some((after_len, after_idx)) if opts.unordered => {
+                    if len < after_len { continue }
+                    if len == after_len { after_idx } else { 0 }
+                },
+                _ => 0,
+            };
+            for (idx, word) in words.iter().enumerate().skip(start) {
+                if prefix_len > 0 {
+                    buf.push_str(&opts.separator);
+                }
+                buf.push_str(word);
+                count += self.write_tuples(opts, out, remaining_len - len, remaining_words - 1, Some((len, idx)), buf)?;
+                buf.truncate(prefix_len);
+            }
+        }
+        Ok(count)
+    }
+}
@@ -0,0 +1,303 @@
+//! `estimate`: predicts how big a search is before running it, using Knuth's
+//! estimator. Each probe walks from the empty square to a random child at
+//! every cell, and the product of the number of choices seen along the way
+//! is an unbiased estimate of the nodes at each depth. Averaging many probes
+//! gives the estimate, with a confidence interval from their spread.
+//!
+//! The estimates are heavy-tailed: most probes hit a dead end early, and the
+//! rare ones that get deep dominate the mean. Treat the interval as a rough
+//! guide, and use more probes when it's wide.
+
+use std::time::{Duration, Instant};
+
+use clap::{Arg, ArgMatches};
+use serde_json::Value;
+
+use super::{compute, CharSet, LoadedWords, WordIndex, ALPHABET_SIZE};
+use super::rng::Rng;
+use super::scheduler::{Donate, Task};
+use super::search_stats::NoStats;
+
+/// z for a two-sided 95% confidence interval.
+const Z_95:f64 = 1.96;
+
+/// How many random subtrees the search speed is measured on.
+const CALIBRATION_RUNS:u32 = 8;
+
+/// Arguments of the estimate subcommand besides the shared wordlist ones.
+pub fn estimate_args<'a, 'b>() -> Vec<Arg<'a, 'b>> {
+    vec![
+        Arg::with_name("probes")
+            .long("probes")
+            .takes_value(true)
+            .default_value("100000")
+            .validator(|arg| {
+                match arg.parse::<u64>() {
+                    Ok(n) if n >= 2 => Ok(()),
+                    Ok(_) => Err(String::from("Must be at least 2")),
+                    Err(e) => Err(format!("Must provide a valid integer. {:?}", e)),
+                }
+            })
+            .help("How many random probes of the search tree to average"),
+        Arg::with_name("seed")
+            .long("seed")
+            .takes_value(true)
+            .validator(|arg| {
+                match arg.parse::<u64>() {
+                    Ok(_) => Ok(()),
+                    Err(e) => Err(format!("Must provide a valid integer. {:?}", e)),
+                }
+            })
+            .help("Seed for the probes, to repeat an estimate. Defaults to one from the clock."),
+        Arg::with_name("calibrate-seconds")
+            .long("calibrate-seconds")
+            .takes_value(true)
+            .default_value("1")
+            .validator(|arg| {
+                match arg.parse::<f64>() {
+                    Ok(n) if n > 0.0 => Ok(()),
+                    Ok(_) => Err(String::from("Must be more than 0")),
+                    Err(e) => Err(format!("Must provide a valid number. {:?}", e)),
+                }
+            })
+            .help("How long to spend measuring search speed on random parts of the tree, to turn the node estimate into a runtime"),
+    ]
+}
+
+pub struct EstimateOptions {
+    pub probes: u64,
+    pub threads: u32,
+    pub calibrate: Duration,
+    rng: Rng,
+}
+
+impl EstimateOptions {
+    pub fn from_args(args:&ArgMatches, threads:u32) -> EstimateOptions {
+        EstimateOptions{
+            probes: args.value_of("probes").unwrap().parse().unwrap(),
+            threads,
+            calibrate: Duration::from_secs_f64(args.value_of("calibrate-seconds").unwrap().parse().unwrap()),
+            rng: match args.value_of("seed") {
+                Some(seed) => Rng::new(seed.parse().unwrap()),
+                None => Rng::from_time(),
+            },
+        }
+    }
+}
+
+/// The mean of some samples and a 95% confidence interval around it.
+#[derive(Debug,Clone,Copy,Default)]
+pub struct Interval {
+    pub mean: f64,
+    pub low: f64,
+    pub high: f64,
+}
+
+/// Accumulates samples with Welford's method, which stays accurate when
+/// they span many orders of magnitude.
+#[derive(Default)]
+struct Samples {
+    count: u64,
+    mean: f64,
+    m2: f64,
+}
+
+impl Samples {
+    fn add(&mut self, x:f64) {
+        self.count += 1;
+        let delta = x - self.mean;
+        self.mean += delta / self.count as f64;
+        self.m2 += delta * (x - self.mean);
+    }
+
+    fn interval(&self) -> Interval {
+        let variance = if self.count > 1 { self.m2 / (self.count - 1) as f64 } else { 0.0 };
+        let margin = Z_95 * (variance / self.count.max(1) as f64).sqrt();
+        Interval{mean: self.mean, low: (self.mean - margin).max(0.0), high: self.mean + margin}
+    }
+}
+
+/// What `estimate` predicts for one size.
+#[derive(Debug,Clone,Default)]
+pub struct Estimate {
+    pub width: usize,
+    pub height: usize,
+    pub row_words: u32,
+    pub col_words: u32,
+    pub probes: u64,
+    pub nodes: Interval,
+    pub squares: Interval,
+    /// Nodes per second on one thread, or 0 if there was nothing to measure.
+    pub rate: f64,
+    pub threads: u32,
+}
+
+pub const COLUMNS:&[&str] = &[
+    "width", "height", "row_words", "col_words", "probes",
+    "nodes", "nodes_low", "nodes_high",
+    "squares", "squares_low", "squares_high",
+    "nodes_per_second", "threads", "seconds", "seconds_low", "seconds_high",
+];
+
+impl Estimate {
+    /// Counts stay floats, as the sizes worth estimating can have more nodes
+    /// than fit in a u64.
+    pub fn row(&self) -> Vec<Value> {
+        let seconds = |nodes:f64| {
+            if self.rate > 0.0 { json!(nodes / (self.rate * self.threads as f64)) } else { Value::Null }
+        };
+        vec![
+            json!(self.width),
+            json!(self.height),
+            json!(self.row_words),
+            json!(self.col_words),
+            json!(self.probes),
+            json!(self.nodes.mean.round()),
+            json!(self.nodes.low.round()),
+            json!(self.nodes.high.round()),
+            json!(self.squares.mean.round()),
+            json!(self.squares.low.round()),
+            json!(self.squares.high.round()),
+            json!(self.rate.round()),
+            json!(self.threads),
+            seconds(self.nodes.mean),
+            seconds(self.nodes.low),
+            seconds(self.nodes.high),
+        ]
+    }
+}
+
+/// The letters allowed at `idx` given the cells before it, as `compute` works them out.
+fn allowed<const W:usize, const H:usize, const S:usize>(index:&WordIndex<W, H>, code_array:&[u8; S], idx:usize) -> CharSet {
+    let (row_idx, col_idx) = (idx / W, idx % W);
+    let mut row_word = [255u8; W];
+    row_word[..col_idx].copy_from_slice(&code_array[row_idx * W..idx]);
+    let mut col_word = [255u8; H];
+    for (i, cell) in col_word.iter_mut().enumerate().take(row_idx) {
+        *cell = code_array[col_idx + i * W];
+    }
+    index.col_set(&col_word).and(&index.row_set(&row_word))
+}
+
+/// One of the letters in `set` at random, which must not be empty.
+fn random_code(set:CharSet, rng:&mut Rng) -> u8 {
+    let mut pick = rng.below(set.internal.count_ones() as u64);
+    for code in 0..ALPHABET_SIZE {
+        if set.has(code) {
+            if pick == 0 {
+                return code;
+            }
+            pick -= 1;
+        }
+    }
+    unreachable!()
+}
+
+/// Walks from the empty square to a random child at each cell until a dead
+/// end or a full square. Gives the estimated node and square counts, and
+/// the walk's prefix at `keep_depth` cells if it got that far.
+fn probe<const W:usize, const H:usize, const S:usize>(
+    index:&WordIndex<W, H>,
+    rng:&mut Rng,
+    keep_depth:usize,
+) -> (f64, f64, Option<[u8; S]>) {
+    let mut code_array = [255u8; S];
+    let mut kept = None;
+    let mut width = 1.0;
+    let mut nodes = 0.0;
+    for idx in 0..S {
+        if idx == keep_depth {
+            kept = Some(code_array);
+        }
+        let set = allowed(index, &code_array, idx);
+        let choices = set.internal.count_ones();
+        if choices == 0 {
+            return (nodes, 0.0, kept);
+        }
+        width *= choices as f64;
+        nodes += width;
+        code_array[idx] = random_code(set, rng);
+    }
+    (nodes, width, kept)
+}
+
+/// Stops a search once its time is up, by having it give away and so drop
+/// everything it hasn't tried yet.
+struct Deadline {
+    at: Instant,
+    dropped: u64,
+}
+
+impl<const S:usize> Donate<S> for Deadline {
+    fn wants_work(&self) -> bool {
+        Instant::now() >= self.at
+    }
+
+    fn donate(&mut self, _task:Task<S>) {
+        self.dropped += 1;
+    }
+}
+
+/// Nodes per second of a single-threaded search, measured by searching from
+/// random prefixes `depth` cells deep for a share of `budget` each.
+fn calibrate<const W:usize, const H:usize, const S:usize>(
+    index:&WordIndex<W, H>,
+    rng:&mut Rng,
+    depth:usize,
+    budget:Duration,
+) -> f64 {
+    let mut visited = 0u64;
+    let mut elapsed = Duration::default();
+    for _ in 0..CALIBRATION_RUNS {
+        // Most probes die early; take the first few that get deep enough.
+        let prefix = match (0..1000).find_map(|_| probe::<W, H, S>(index, rng, depth).2) {
+            Some(prefix) => prefix,
+            None => break,
+        };
+        let start = Instant::now();
+        let mut deadline = Deadline{at: start + budget / CALIBRATION_RUNS, dropped: 0};
+        let nodes = compute(index, prefix, depth as u8, S as u8, |_, _| (), &mut deadline, &mut NoStats);
+        elapsed += start.elapsed();
+        visited += nodes - deadline.dropped;
+    }
+    if visited == 0 {
+        0.0
+    } else {
+        visited as f64 / elapsed.as_secs_f64().max(1e-9)
+    }
+}
+
+/// Indexes `words` for a `W` by `H` grid and estimates its search.
+pub fn estimate_size<const W:usize, const H:usize, const S:usize>(
+    words:&LoadedWords,
+    opts:&mut EstimateOptions,
+) -> Estimate {
+    let (row_words, col_words, index) = words.index::<W, H>();
+    estimate_index::<W, H, S>(&index, row_words, col_words, opts)
+}
+
+pub fn estimate_index<const W:usize, const H:usize, const S:usize>(
+    index:&WordIndex<W, H>,
+    row_words:u32,
+    col_words:u32,
+    opts:&mut EstimateOptions,
+) -> Estimate {
+    let mut estimate = Estimate{width: W, height: H, row_words, col_words, threads: opts.threads, ..Default::default()};
+    if index.is_empty() {
+        return estimate;
+    }
+    let mut nodes = Samples::default();
+    let mut squares = Samples::default();
+    for _ in 0..opts.probes {
+        let (n, s, _) = probe::<W, H, S>(index, &mut opts.rng, S);
+        nodes.add(n);
+        squares.add(s);
+    }
+    estimate.probes = opts.probes;
+    estimate.nodes = nodes.interval();
+    estimate.squares = squares.interval();
+    // Starting a row down keeps calibration runs off the few shallow
+    // subtrees every search starts in.
+    estimate.rate = calibrate::<W, H, S>(index, &mut opts.rng, W.min(S - 1), opts.calibrate);
+    estimate
+}
@@ -0,0 +1,203 @@
+//! Word filters shared by wordlist-preprocess, build-index and compute: length
+//! bounds, include/exclude regexes, block/allow lists, and frequency and part
+//! of speech from `--columns` metadata.
+
+use std::collections::BTreeMap;
+use std::io;
+use std::io::prelude::*;
+
+use clap::{Arg, ArgMatches};
+use fnv::FnvHashSet;
+use regex::Regex;
+
+use super::invalid_data;
+use super::columns::Schema;
+use super::input::open_input;
+use super::text::TextOptions;
+
+/// Arguments read by `WordFilter::from_args`.
+pub fn filter_args<'a, 'b>() -> Vec<Arg<'a, 'b>> {
+    let integer = |arg:String| {
+        match arg.parse::<usize>() {
+            Ok(_) => Ok(()),
+            Err(e) => Err(format!("Must provide a valid integer. {:?}", e)),
+        }
+    };
+    vec![
+        Arg::with_name("min-len")
+            .long("min-len")
+            .takes_value(true)
+            .validator(integer)
+            .help("Drop words with fewer characters than this"),
+        Arg::with_name("max-len")
+            .long("max-len")
+            .takes_value(true)
+            .validator(integer)
+            .help("Drop words with more characters than this"),
+        Arg::with_name("include-regex")
+            .long("include-regex")
+            .takes_value(true)
+            .help("Only keep words matching this regex. Use ^ and $ to match the whole word."),
+        Arg::with_name("exclude-regex")
+            .long("exclude-regex")
+            .takes_value(true)
+            .help("Drop words matching this regex"),
+        Arg::with_name("blocklist")
+            .long("blocklist")
+            .takes_value(true)
+            .multiple(true)
+            .number_of_values(1)
+            .help("Drop words listed in this file, one per line. May be given more than once."),
+        Arg::with_name("allowlist")
+            .long("allowlist")
+            .takes_value(true)
+            .multiple(true)
+            .number_of_values(1)
+            .help("Only keep words listed in this file, one per line. May be given more than once."),
+        Arg::with_name("min-freq")
+            .long("min-freq")
+            .takes_value(true)
+            .validator(|arg| {
+                match arg.parse::<f64>() {
+                    Ok(_) => Ok(()),
+                    Err(e) => Err(format!("Must provide a valid number. {:?}", e)),
+                }
+            })
+            .help("Drop words whose freq column is below this. Needs a \"freq\" column in --columns."),
+        Arg::with_name("pos")
+            .long("pos")
+            .takes_value(true)
+            .multiple(true)
+            .require_delimiter(true)
+            .help("Only keep words with one of these parts of speech, eg \"noun,verb\". Needs a \"pos\" column in --columns, or wiktextract input."),
+    ]
+}
+
+fn read_list(paths:clap::Values, text:&TextOptions) -> io::Result<FnvHashSet<String>> {
+    let mut words = FnvHashSet::default();
+    for path in paths {
+        for line in open_input(path)?.lines() {
+            let word = text.apply(line?.trim());
+            if !word.is_empty() {
+                words.insert(word);
+            }
+        }
+    }
+    Ok(words)
+}
+
+fn parse_regex(args:&ArgMatches, name:&str) -> io::Result<Option<Regex>> {
+    match args.value_of(name) {
+        Some(pattern) => Regex::new(pattern)
+            .map(Some)
+            .map_err(|e| invalid_data(&format!("invalid --{}: {}", name, e))),
+        None => Ok(None),
+    }
+}
+
+#[derive(Debug,Default)]
+pub struct WordFilter {
+    min_len: Option<usize>,
+    max_len: Option<usize>,
+    include: Option<Regex>,
+    exclude: Option<Regex>,
+    blocklist: FnvHashSet<String>,
+    allowlist: Option<FnvHashSet<String>>,
+    /// Metadata column index and minimum value.
+    min_freq: Option<(usize,f64)>,
+    /// Metadata column index and allowed values.
+    pos: Option<(usize,FnvHashSet<String>)>,
+    removed: BTreeMap<&'static str,u64>,
+}
+
+impl WordFilter {
+    /// Reads the filter arguments; list entries are converted with `text` so
+    /// they compare equal to words converted the same way. `schema` gives the
+    /// metadata columns, if the wordlist has any.
+    pub fn from_args(args:&ArgMatches, text:&TextOptions, schema:Option<&Schema>) -> io::Result<WordFilter> {
+        let min_freq = match args.value_of("min-freq") {
+            Some(min) => match schema.and_then(|s| s.meta_index("freq")) {
+                Some(col) => Some((col, min.parse().unwrap())),
+                None => return Err(invalid_data("--min-freq needs a \"freq\" column in --columns")),
+            },
+            None => None,
+        };
+        let pos = match args.values_of("pos") {
+            Some(values) => match schema.and_then(|s| s.meta_index("pos")) {
+                Some(col) => Some((col, values.map(String::from).collect())),
+                // wiktextract entries are filtered on their own part of speech.
+                None if args.is_present("wiktextract-format") => None,
+                None => return Err(invalid_data("--pos needs a \"pos\" column in --columns")),
+            },
+            None => None,
+        };
+        Ok(WordFilter{
+            min_len: args.value_of("min-len").map(|l| l.parse().unwrap()),
+            max_len: args.value_of("max-len").map(|l| l.parse().unwrap()),
+            include: parse_regex(args, "include-regex")?,
+            exclude: parse_regex(args, "exclude-regex")?,
+            blocklist: match args.values_of("blocklist") {
+                Some(paths) => read_list(paths, text)?,
+                None => FnvHashSet::default(),
+            },
+            allowlist: match args.values_of("allowlist") {
+                Some(paths) => Some(read_list(paths, text)?),
+                None => None,
+            },
+            min_freq,
+            pos,
+            removed: BTreeMap::new(),
+        })
+    }
+
+    fn rejection(&self, word:&str, meta:&[String]) -> Option<&'static str> {
+        let len = word.chars().count();
+        if self.min_len.is_some_and(|min| len < min) {
+            return Some("shorter than --min-len");
+        }
+        if self.max_len.is_some_and(|max| len > max) {
+            return Some("longer than --max-len");
+        }
+        if self.include.as_ref().is_some_and(|re| !re.is_match(word)) {
+            return Some("not matching --include-regex");
+        }
+        if self.exclude.as_ref().is_some_and(|re| re.is_match(word)) {
+            return Some("matching --exclude-regex");
+        }
+        if self.blocklist.contains(word) {
+            return Some("in --blocklist");
+        }
+        if self.allowlist.as_ref().is_some_and(|allowed| !allowed.contains(word)) {
+            return Some("not in --allowlist");
+        }
+        if let Some((col, min)) = self.min_freq {
+            let freq = meta.get(col).and_then(|f| f.trim().parse::<f64>().ok());
+            if !freq.is_some_and(|freq| freq >= min) {
+                return Some("below --min-freq");
+            }
+        }
+        if let Some((col, ref allowed)) = self.pos {
+            if !meta.get(col).is_some_and(|pos| allowed.contains(pos.trim())) {
+                return Some("not matching --pos");
+            }
+        }
+        None
+    }
+
+    /// Gives the reason `word` is filtered out, if it is, and counts it.
+    /// `meta` holds the word's metadata values, empty without --columns.
+    pub fn check(&mut self, word:&str, meta:&[String]) -> Option<&'static str> {
+        let reason = self.rejection(word, meta);
+        if let Some(reason) = reason {
+            *self.removed.entry(reason).or_insert(0) += 1;
+        }
+        reason
+    }
+
+    /// Prints how many words each filter removed to STDERR.
+    pub fn report(&self) {
+        for (reason, count) in &self.removed {
+            eprintln!("Filtered out {} words {}.", count, reason);
+        }
+    }
+}
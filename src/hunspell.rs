@@ -0,0 +1,438 @@
+//! Expands Hunspell .dic/.aff pairs into full word forms.
+//!
+//! Only the parts of the affix file that produce word forms are understood:
+//! SET, FLAG, AF, PFX, SFX, NEEDAFFIX, ONLYINCOMPOUND and FORBIDDENWORD.
+//! Suffixes may carry continuation flags, which are applied once more to the
+//! suffixed form. Compounding rules are ignored.
+
+use std::collections::VecDeque;
+use std::io;
+use std::io::prelude::*;
+
+use fnv::{FnvHashMap, FnvHashSet};
+
+use super::invalid_data;
+
+type Flag = u64;
+
+#[derive(Debug,Clone,Copy,PartialEq,Eq)]
+enum Encoding {
+    Utf8,
+    Latin1,
+}
+
+impl Encoding {
+    fn from_name(name:&str) -> io::Result<Encoding> {
+        match name.to_uppercase().as_str() {
+            "UTF-8" | "UTF8" => Ok(Encoding::Utf8),
+            "ISO8859-1" | "ISO-8859-1" | "LATIN1" => Ok(Encoding::Latin1),
+            other => Err(invalid_data(&format!("unsupported hunspell encoding {}", other))),
+        }
+    }
+
+    fn decode(self, bytes:&[u8]) -> io::Result<String> {
+        match self {
+            Encoding::Utf8 => String::from_utf8(bytes.to_vec())
+                .map_err(|_| invalid_data("hunspell file is not valid UTF-8")),
+            Encoding::Latin1 => Ok(bytes.iter().map(|&b| b as char).collect()),
+        }
+    }
+}
+
+#[derive(Debug,Clone,Copy,PartialEq,Eq)]
+enum FlagType {
+    /// One character per flag.
+    Short,
+    /// Two characters per flag.
+    Long,
+    /// Comma-separated decimal numbers.
+    Num,
+}
+
+impl FlagType {
+    fn parse(self, s:&str) -> Vec<Flag> {
+        match self {
+            FlagType::Short => s.chars().map(|c| c as Flag).collect(),
+            FlagType::Long => {
+                let chars:Vec<char> = s.chars().collect();
+                chars.chunks(2).map(|pair| {
+                    ((pair[0] as Flag) << 32) | pair.get(1).map(|&c| c as Flag).unwrap_or(0)
+                }).collect()
+            },
+            FlagType::Num => s.split(',').filter_map(|n| n.trim().parse().ok()).collect(),
+        }
+    }
+}
+
+#[derive(Debug,Clone,PartialEq,Eq)]
+enum CondElem {
+    Any,
+    Char(char),
+    Set(Vec<char>, bool),
+}
+
+impl CondElem {
+    fn matches(&self, c:char) -> bool {
+        match *self {
+            CondElem::Any => true,
+            CondElem::Char(want) => c == want,
+            CondElem::Set(ref chars, negated) => chars.contains(&c) != negated,
+        }
+    }
+}
+
+fn parse_condition(cond:&str) -> Vec<CondElem> {
+    let mut res = Vec::new();
+    let mut chars = cond.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '.' => res.push(CondElem::Any),
+            '[' => {
+                let mut set = Vec::new();
+                let mut negated = false;
+                for c in chars.by_ref() {
+                    match c {
+                        ']' => break,
+                        '^' if set.is_empty() && !negated => negated = true,
+                        c => set.push(c),
+                    }
+                }
+                res.push(CondElem::Set(set, negated));
+            },
+            c => res.push(CondElem::Char(c)),
+        }
+    }
+    res
+}
+
+#[derive(Debug,Clone)]
+struct AffixRule {
+    strip: String,
+    add: String,
+    continuation: Vec<Flag>,
+    condition: Vec<CondElem>,
+}
+
+#[derive(Debug,Clone)]
+struct AffixClass {
+    cross_product: bool,
+    rules: Vec<AffixRule>,
+}
+
+#[derive(Debug)]
+pub struct Affixes {
+    encoding: Encoding,
+    flag_type: FlagType,
+    aliases: Vec<Vec<Flag>>,
+    prefixes: FnvHashMap<Flag,AffixClass>,
+    suffixes: FnvHashMap<Flag,AffixClass>,
+    /// Flags marking roots that must not appear on their own.
+    root_excluding: FnvHashSet<Flag>,
+}
+
+fn read_lines(input:impl BufRead) -> io::Result<Vec<Vec<u8>>> {
+    let mut lines = Vec::new();
+    for line in input.split(b'\n') {
+        let mut line = line?;
+        if lines.is_empty() && line.starts_with(&[0xef, 0xbb, 0xbf]) {
+            line.drain(0..3);
+        }
+        while line.last() == Some(&b'\r') {
+            line.pop();
+        }
+        lines.push(line);
+    }
+    Ok(lines)
+}
+
+impl Affixes {
+    pub fn parse(input:impl BufRead) -> io::Result<Affixes> {
+        let mut affixes = Affixes{
+            encoding: Encoding::Latin1,
+            flag_type: FlagType::Short,
+            aliases: Vec::new(),
+            prefixes: FnvHashMap::default(),
+            suffixes: FnvHashMap::default(),
+            root_excluding: FnvHashSet::default(),
+        };
+        let mut root_excluding_names = Vec::new();
+        let mut seen_alias_count = false;
+        for raw in read_lines(input)? {
+            let line = affixes.encoding.decode(&raw)?;
+            let fields:Vec<&str> = line.split_whitespace().collect();
+            match fields.as_slice() {
+                ["SET", name, ..] => affixes.encoding = Encoding::from_name(name)?,
+                ["FLAG", "long", ..] => affixes.flag_type = FlagType::Long,
+                ["FLAG", "num", ..] => affixes.flag_type = FlagType::Num,
+                ["FLAG", ..] => affixes.flag_type = FlagType::Short,
+                // The first AF line only gives the number of aliases.
+                ["AF", _, ..] if !seen_alias_count => seen_alias_count = true,
+                ["AF", flags, ..] => {
+                    let parsed = affixes.flag_type.parse(flags);
+                    affixes.aliases.push(parsed);
+                },
+                ["NEEDAFFIX", flag, ..]
+                | ["ONLYINCOMPOUND", flag, ..]
+                | ["FORBIDDENWORD", flag, ..] => root_excluding_names.push(flag.to_string()),
+                [kind @ "PFX", flag, cross, count] | [kind @ "SFX", flag, cross, count]
+                    if (*cross == "Y" || *cross == "N") && count.parse::<usize>().is_ok() => {
+                    let class = AffixClass{ cross_product: *cross == "Y", rules: Vec::new() };
+                    let flag = affixes.single_flag(flag);
+                    if *kind == "PFX" {
+                        affixes.prefixes.insert(flag, class);
+                    } else {
+                        affixes.suffixes.insert(flag, class);
+                    }
+                },
+                [kind @ "PFX", flag, strip, add, rest @ ..] | [kind @ "SFX", flag, strip, add, rest @ ..] => {
+                    let (add, continuation) = match add.find('/') {
+                        Some(i) => (&add[..i], affixes.parse_flags(&add[i+1..])),
+                        None => (*add, Vec::new()),
+                    };
+                    let rule = AffixRule{
+                        strip: if *strip == "0" { String::new() } else { strip.to_string() },
+                        add: if add == "0" { String::new() } else { add.to_string() },
+                        continuation,
+                        condition: parse_condition(rest.first().cloned().unwrap_or(".")),
+                    };
+                    let flag = affixes.single_flag(flag);
+                    let classes = if *kind == "PFX" { &mut affixes.prefixes } else { &mut affixes.suffixes };
+                    match classes.get_mut(&flag) {
+                        Some(class) => class.rules.push(rule),
+                        None => return Err(invalid_data(&format!("{} rule before its header: {}", kind, line))),
+                    }
+                },
+                _ => (),
+            }
+        }
+        for name in root_excluding_names {
+            let flag = affixes.single_flag(&name);
+            affixes.root_excluding.insert(flag);
+        }
+        Ok(affixes)
+    }
+
+    fn single_flag(&self, s:&str) -> Flag {
+        self.flag_type.parse(s).first().cloned().unwrap_or(0)
+    }
+
+    /// Parses a flag field, resolving AF aliases if the file defines any.
+    fn parse_flags(&self, s:&str) -> Vec<Flag> {
+        if !self.aliases.is_empty() {
+            if let Ok(n) = s.parse::<usize>() {
+                return self.aliases.get(n.wrapping_sub(1)).cloned().unwrap_or_default();
+            }
+        }
+        self.flag_type.parse(s)
+    }
+
+    fn apply_suffix(&self, word:&str, rule:&AffixRule) -> Option<String> {
+        let chars:Vec<char> = word.chars().collect();
+        if chars.len() < rule.condition.len() || !word.ends_with(rule.strip.as_str()) {
+            return None;
+        }
+        let tail = &chars[chars.len() - rule.condition.len()..];
+        if !rule.condition.iter().zip(tail).all(|(cond, &c)| cond.matches(c)) {
+            return None;
+        }
+        let stem = &word[..word.len() - rule.strip.len()];
+        if stem.is_empty() && rule.add.is_empty() {
+            return None;
+        }
+        Some(format!("{}{}", stem, rule.add))
+    }
+
+    fn apply_prefix(&self, word:&str, rule:&AffixRule) -> Option<String> {
+        let chars:Vec<char> = word.chars().collect();
+        if chars.len() < rule.condition.len() || !word.starts_with(rule.strip.as_str()) {
+            return None;
+        }
+        if !rule.condition.iter().zip(&chars).all(|(cond, &c)| cond.matches(c)) {
+            return None;
+        }
+        let stem = &word[rule.strip.len()..];
+        if stem.is_empty() && rule.add.is_empty() {
+            return None;
+        }
+        Some(format!("{}{}", rule.add, stem))
+    }
+
+    /// Every form of `root` generated by `flags`, including the root itself
+    /// unless one of the flags forbids it.
+    fn expand(&self, root:&str, flags:&[Flag], out:&mut VecDeque<String>) {
+        if !flags.iter().any(|f| self.root_excluding.contains(f)) {
+            out.push_back(root.to_string());
+        }
+        let mut cross_suffixed = Vec::new();
+        for flag in flags {
+            let class = match self.suffixes.get(flag) {
+                Some(class) => class,
+                None => continue,
+            };
+            for rule in &class.rules {
+                let form = match self.apply_suffix(root, rule) {
+                    Some(form) => form,
+                    None => continue,
+                };
+                for cont in &rule.continuation {
+                    if let Some(cont_class) = self.suffixes.get(cont) {
+                        for cont_rule in &cont_class.rules {
+                            out.extend(self.apply_suffix(&form, cont_rule));
+                        }
+                    }
+                }
+                if class.cross_product {
+                    cross_suffixed.push(form.clone());
+                }
+                if !rule.continuation.iter().any(|f| self.root_excluding.contains(f)) {
+                    out.push_back(form);
+                }
+            }
+        }
+        for flag in flags {
+            let class = match self.prefixes.get(flag) {
+                Some(class) => class,
+                None => continue,
+            };
+            for rule in &class.rules {
+                match self.apply_prefix(root, rule) {
+                    Some(form) => out.push_back(form),
+                    None => continue,
+                }
+                if class.cross_product {
+                    for form in &cross_suffixed {
+                        // The prefix condition was checked against the root
+                        // above, but a suffix that strips most of a short root
+                        // can change how the form starts.
+                        if let Some(stem) = form.strip_prefix(rule.strip.as_str()) {
+                            out.push_back(format!("{}{}", rule.add, stem));
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    /// Reads a .dic file and yields every word form it describes.
+    pub fn expand_dic<R:BufRead>(self, dic:R) -> DicExpander<R> {
+        DicExpander{ affixes: self, lines: dic.split(b'\n'), first_line: true, pending: VecDeque::new() }
+    }
+}
+
+pub struct DicExpander<R:BufRead> {
+    affixes: Affixes,
+    lines: io::Split<R>,
+    first_line: bool,
+    pending: VecDeque<String>,
+}
+
+impl<R:BufRead> Iterator for DicExpander<R> {
+    type Item = io::Result<String>;
+
+    fn next(&mut self) -> Option<io::Result<String>> {
+        loop {
+            if let Some(word) = self.pending.pop_front() {
+                return Some(Ok(word));
+            }
+            let mut raw = match self.lines.next()? {
+                Ok(raw) => raw,
+                Err(e) => return Some(Err(e)),
+            };
+            if self.first_line && raw.starts_with(&[0xef, 0xbb, 0xbf]) {
+                raw.drain(0..3);
+            }
+            while raw.last() == Some(&b'\r') {
+                raw.pop();
+            }
+            let line = match self.affixes.encoding.decode(&raw) {
+                Ok(line) => line,
+                Err(e) => return Some(Err(e)),
+            };
+            if self.first_line {
+                self.first_line = false;
+                // The first line is an approximate word count.
+                if line.trim().parse::<u64>().is_ok() {
+                    continue;
+                }
+            }
+            let entry = line.split('\t').next().unwrap().trim();
+            let entry = entry.split_whitespace().next().unwrap_or("");
+            if entry.is_empty() || entry.starts_with('#') {
+                continue;
+            }
+            let slash = entry.char_indices()
+                .find(|&(i, c)| c == '/' && (i == 0 || !entry[..i].ends_with('\\')))
+                .map(|(i, _)| i);
+            let (root, flags) = match slash {
+                Some(i) => (entry[..i].replace("\\/", "/"), self.affixes.parse_flags(&entry[i+1..])),
+                None => (entry.replace("\\/", "/"), Vec::new()),
+            };
+            self.affixes.expand(&root, &flags, &mut self.pending);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The forms `root` with `flags` expands to under the affix file `aff`, sorted.
+    fn forms(aff:&str, root:&str, flags:&str) -> Vec<String> {
+        let affixes = Affixes::parse(aff.as_bytes()).unwrap();
+        let flags = affixes.parse_flags(flags);
+        let mut out = VecDeque::new();
+        affixes.expand(root, &flags, &mut out);
+        let mut out:Vec<String> = out.into_iter().collect();
+        out.sort();
+        out
+    }
+
+    #[test]
+    fn suffix() {
+        let aff = "SFX S Y 1\nSFX S 0 s .\n";
+        assert_eq!(forms(aff, "cat", "S"), ["cat", "cats"]);
+    }
+
+    #[test]
+    fn suffix_strip_and_condition() {
+        let aff = "SFX D Y 2\nSFX D y ied [^aeiou]y\nSFX D 0 ed [aeiou]y\n";
+        assert_eq!(forms(aff, "try", "D"), ["tried", "try"]);
+        assert_eq!(forms(aff, "play", "D"), ["play", "played"]);
+    }
+
+    #[test]
+    fn prefix() {
+        let aff = "PFX U Y 1\nPFX U 0 un .\n";
+        assert_eq!(forms(aff, "do", "U"), ["do", "undo"]);
+    }
+
+    #[test]
+    fn cross_product() {
+        let aff = "PFX U Y 1\nPFX U 0 un .\nSFX S Y 1\nSFX S 0 s .\n";
+        assert_eq!(forms(aff, "do", "US"), ["do", "dos", "undo", "undos"]);
+        let aff = "PFX U N 1\nPFX U 0 un .\nSFX S Y 1\nSFX S 0 s .\n";
+        assert_eq!(forms(aff, "do", "US"), ["do", "dos", "undo"]);
+    }
+
+    #[test]
+    fn cross_product_skips_forms_the_prefix_strip_no_longer_fits() {
+        // The suffix replaces the whole root, so the prefix's strip of "a"
+        // only fits the root, and the multibyte form must not be cut into.
+        let aff = "SET UTF-8\nPFX P Y 1\nPFX P a z a\nSFX X Y 1\nSFX X ab ñ .\n";
+        assert_eq!(forms(aff, "ab", "PX"), ["ab", "zb", "ñ"]);
+    }
+
+    #[test]
+    fn continuation() {
+        let aff = "SFX A Y 1\nSFX A 0 ing/B .\nSFX B Y 1\nSFX B 0 s .\n";
+        assert_eq!(forms(aff, "walk", "A"), ["walk", "walking", "walkings"]);
+    }
+
+    #[test]
+    fn needaffix() {
+        let aff = "NEEDAFFIX N\nSFX A Y 1\nSFX A 0 ing/BN .\nSFX B Y 1\nSFX B 0 s .\n";
+        assert_eq!(forms(aff, "walk", "A"), ["walk", "walkings"]);
+        let aff = "NEEDAFFIX N\nSFX S Y 1\nSFX S 0 s .\n";
+        assert_eq!(forms(aff, "cat", "SN"), ["cats"]);
+    }
+}
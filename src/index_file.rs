@@ -0,0 +1,302 @@
+//! On-disk format for a built `WordIndex`, so repeated runs can skip re-reading
+//! and re-indexing the wordlist.
+//!
+//! ```text
+//! magic      4 bytes  "RWSI"
+//! version    u8       currently 1
+//! width      u8
+//! height     u8
+//! flags      u8       bit 0: width equals height
+//!                     bit 1: square with a separate cols section
+//!                     bit 2: has a meta section
+//! alpha_len  u16 LE   length in bytes of the alphabet string
+//! alphabet   UTF-8    one char per code, in code order
+//! row_words  u32 LE
+//! col_words  u32 LE
+//! rows       section
+//! cols       section, present for non-square grids or if flag bit 1 is set
+//! meta       present if flag bit 2 is set
+//! ```
+//!
+//! A section is a u64 LE entry count followed by that many entries, each being
+//! the prefix key (one code per letter, 255 for unfilled) and its CharSet, LE.
+//! CharSets take 4 bytes, or 8 with the phrases feature's 33-symbol alphabet;
+//! the alphabet check on load means the reader always agrees with the writer.
+//!
+//! The meta section keeps `--columns` metadata: a u16 LE column count and each
+//! column name, then a u64 LE word count and for each word a u8 length, its
+//! codes, and one value per column. Names and values are UTF-8 prefixed with
+//! their length, as u16 LE and u32 LE respectively.
+//!
+//! The index is only usable by a binary with the same dimensions and
+//! alphabet, which is checked on load.
+
+use std::io;
+use std::io::prelude::*;
+use std::mem;
+
+use fnv::FnvHashMap;
+
+use super::{alphabet, invalid_data, CharSet, CharSetBits, WordIndex, WordMetadata};
+
+const MAGIC:&[u8; 4] = b"RWSI";
+const VERSION:u8 = 1;
+
+fn read_u32(input:&mut impl Read) -> io::Result<u32> {
+    let mut buf = [0u8; 4];
+    input.read_exact(&mut buf)?;
+    Ok(u32::from_le_bytes(buf))
+}
+
+fn read_u64(input:&mut impl Read) -> io::Result<u64> {
+    let mut buf = [0u8; 8];
+    input.read_exact(&mut buf)?;
+    Ok(u64::from_le_bytes(buf))
+}
+
+fn read_u16(input:&mut impl Read) -> io::Result<u16> {
+    let mut buf = [0u8; 2];
+    input.read_exact(&mut buf)?;
+    Ok(u16::from_le_bytes(buf))
+}
+
+fn read_string(input:&mut impl Read, len:usize) -> io::Result<String> {
+    let mut buf = vec![0u8; len];
+    input.read_exact(&mut buf)?;
+    String::from_utf8(buf).map_err(|_| invalid_data("index metadata is not valid UTF-8"))
+}
+
+fn write_meta(out:&mut impl Write, meta:&WordMetadata) -> io::Result<()> {
+    out.write_all(&(meta.columns.len() as u16).to_le_bytes())?;
+    for name in &meta.columns {
+        out.write_all(&(name.len() as u16).to_le_bytes())?;
+        out.write_all(name.as_bytes())?;
+    }
+    out.write_all(&(meta.len() as u64).to_le_bytes())?;
+    for (codes, values) in meta.iter() {
+        out.write_all(&[codes.len() as u8])?;
+        out.write_all(codes)?;
+        for value in values {
+            out.write_all(&(value.len() as u32).to_le_bytes())?;
+            out.write_all(value.as_bytes())?;
+        }
+    }
+    Ok(())
+}
+
+fn read_meta(input:&mut impl Read) -> io::Result<WordMetadata> {
+    let num_columns = read_u16(input)? as usize;
+    let mut columns = Vec::with_capacity(num_columns);
+    for _ in 0..num_columns {
+        let len = read_u16(input)? as usize;
+        columns.push(read_string(input, len)?);
+    }
+    let mut meta = WordMetadata::new(columns);
+    for _ in 0..read_u64(input)? {
+        let mut len = [0u8; 1];
+        input.read_exact(&mut len)?;
+        let mut codes = vec![0u8; len[0] as usize];
+        input.read_exact(&mut codes)?;
+        let mut values = Vec::with_capacity(num_columns);
+        for _ in 0..num_columns {
+            let len = read_u32(input)? as usize;
+            values.push(read_string(input, len)?);
+        }
+        meta.insert(&codes, values);
+    }
+    Ok(meta)
+}
+
+fn write_section<K:AsRef<[u8]>>(
+    out:&mut impl Write,
+    map:&FnvHashMap<K,CharSet>,
+) -> io::Result<()> {
+    out.write_all(&(map.len() as u64).to_le_bytes())?;
+    for (key, set) in map {
+        out.write_all(key.as_ref())?;
+        out.write_all(&set.internal.to_le_bytes())?;
+    }
+    Ok(())
+}
+
+fn read_section<const N:usize>(
+    input:&mut impl Read,
+) -> io::Result<FnvHashMap<[u8; N],CharSet>> {
+    let len = read_u64(input)?;
+    // The count comes from the file, so a corrupt one shouldn't be able to
+    // make us allocate more than a modest amount before entries run out.
+    let mut map = FnvHashMap::with_capacity_and_hasher(len.min(1 << 20) as usize, Default::default());
+    for _ in 0..len {
+        let mut key = [0u8; N];
+        input.read_exact(&mut key)?;
+        let mut set = [0u8; mem::size_of::<CharSetBits>()];
+        input.read_exact(&mut set)?;
+        let set = CharSet::new(CharSetBits::from_le_bytes(set));
+        map.insert(key, set);
+    }
+    Ok(map)
+}
+
+pub fn write_index<const W:usize, const H:usize>(
+    out:&mut impl Write,
+    count_row_words:u32,
+    count_col_words:u32,
+    index:&WordIndex<W, H>,
+) -> io::Result<()> {
+    let alphabet:String = alphabet().into_iter().collect();
+    out.write_all(MAGIC)?;
+    let square = W == H;
+    let separate_cols = square && index.has_separate_cols();
+    out.write_all(&[
+        VERSION,
+        W as u8,
+        H as u8,
+        (square as u8) | ((separate_cols as u8) << 1) | ((!index.meta.is_empty() as u8) << 2),
+    ])?;
+    out.write_all(&(alphabet.len() as u16).to_le_bytes())?;
+    out.write_all(alphabet.as_bytes())?;
+    out.write_all(&count_row_words.to_le_bytes())?;
+    out.write_all(&count_col_words.to_le_bytes())?;
+    write_section(out, index.rows())?;
+    if let Some(cols) = index.separate_cols() {
+        write_section(out, cols)?;
+    }
+    if !index.meta.is_empty() {
+        write_meta(out, &index.meta)?;
+    }
+    out.flush()
+}
+
+pub fn read_index<const W:usize, const H:usize>(input:&mut impl Read) -> io::Result<(u32, u32, WordIndex<W, H>)> {
+    let mut fixed = [0u8; 10];
+    input.read_exact(&mut fixed)?;
+    if &fixed[0..4] != MAGIC {
+        return Err(invalid_data("not an index file (bad magic)"));
+    }
+    if fixed[4] != VERSION {
+        return Err(invalid_data(&format!("unsupported index file version {}, rebuild it with build-index", fixed[4])));
+    }
+    let (width, height) = (fixed[5] as usize, fixed[6] as usize);
+    let square = fixed[7] & 1 != 0;
+    let separate_cols = !square || fixed[7] & 2 != 0;
+    let has_meta = fixed[7] & 4 != 0;
+    if width != W || height != H {
+        return Err(invalid_data(&format!(
+            "index was built for {}x{}, but this binary is {}x{}",
+            width, height, W, H,
+        )));
+    }
+    let alpha_len = u16::from_le_bytes([fixed[8], fixed[9]]) as usize;
+    let mut alphabet_bytes = vec![0u8; alpha_len];
+    input.read_exact(&mut alphabet_bytes)?;
+    let expected:String = alphabet().into_iter().collect();
+    if alphabet_bytes != expected.as_bytes() {
+        return Err(invalid_data("index was built with a different alphabet, eg with or without the phrases feature"));
+    }
+    let count_row_words = read_u32(input)?;
+    let count_col_words = read_u32(input)?;
+
+    let mut index = WordIndex::default();
+    *index.rows_mut() = read_section(input)?;
+    if separate_cols {
+        *index.cols_mut() = read_section(input)?;
+    }
+    if has_meta {
+        index.meta = read_meta(input)?;
+    }
+    Ok((count_row_words, count_col_words, index))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::super::{EncodedWord, LoadedWords};
+
+    /// Words of `codes`, each with the given metadata values.
+    fn words(codes:&[&[u8]], meta:&[&str]) -> Vec<EncodedWord> {
+        codes.iter().map(|c| EncodedWord{
+            codes: c.to_vec(),
+            meta: meta.iter().map(|m| m.to_string()).collect(),
+        }).collect()
+    }
+
+    fn round_trip<const W:usize, const H:usize>(words:&LoadedWords) -> (WordIndex<W, H>, (u32, u32, WordIndex<W, H>)) {
+        let (row_words, col_words, index) = words.index::<W, H>();
+        let mut file = Vec::new();
+        write_index(&mut file, row_words, col_words, &index).unwrap();
+        let read = read_index::<W, H>(&mut &file[..]).unwrap();
+        assert_eq!((read.0, read.1), (row_words, col_words));
+        (index, read)
+    }
+
+    #[test]
+    fn square_shared() {
+        let loaded = LoadedWords{
+            meta_columns: Vec::new(),
+            rows: words(&[&[0, 1, 2], &[1, 0, 3], &[2, 3, 0]], &[]),
+            cols: None,
+            max_memory: None,
+        };
+        let (index, (_, _, read)) = round_trip::<3, 3>(&loaded);
+        assert_eq!(read.rows(), index.rows());
+        assert!(!read.has_separate_cols());
+        assert!(read.meta.is_empty());
+    }
+
+    #[test]
+    fn square_separate_cols() {
+        let loaded = LoadedWords{
+            meta_columns: Vec::new(),
+            rows: words(&[&[0, 1, 2], &[1, 0, 3]], &[]),
+            cols: Some(words(&[&[2, 3, 0]], &[])),
+            max_memory: None,
+        };
+        let (index, (row_words, col_words, read)) = round_trip::<3, 3>(&loaded);
+        assert_eq!((row_words, col_words), (2, 1));
+        assert_eq!(read.rows(), index.rows());
+        assert!(read.has_separate_cols());
+        assert_eq!(read.separate_cols(), index.separate_cols());
+    }
+
+    #[test]
+    fn rectangle() {
+        let loaded = LoadedWords{
+            meta_columns: Vec::new(),
+            rows: words(&[&[0, 1, 2, 3], &[4, 5, 6], &[6, 5, 4]], &[]),
+            cols: None,
+            max_memory: None,
+        };
+        let (index, (_, _, read)) = round_trip::<4, 3>(&loaded);
+        assert_eq!(read.rows(), index.rows());
+        assert_eq!(read.separate_cols(), index.separate_cols());
+    }
+
+    #[test]
+    fn with_meta() {
+        let loaded = LoadedWords{
+            meta_columns: vec![String::from("freq"), String::from("pos")],
+            rows: words(&[&[0, 1, 2], &[1, 0, 3]], &["12", "noun"]),
+            cols: None,
+            max_memory: None,
+        };
+        let (index, (_, _, read)) = round_trip::<3, 3>(&loaded);
+        assert_eq!(read.rows(), index.rows());
+        assert_eq!(read.meta.columns, loaded.meta_columns);
+        assert_eq!(read.meta.len(), 2);
+        assert_eq!(read.meta.get(&[1, 0, 3]), Some(&vec![String::from("12"), String::from("noun")]));
+    }
+
+    #[test]
+    fn wrong_size() {
+        let loaded = LoadedWords{
+            meta_columns: Vec::new(),
+            rows: words(&[&[0, 1, 2]], &[]),
+            cols: None,
+            max_memory: None,
+        };
+        let (row_words, col_words, index) = loaded.index::<3, 3>();
+        let mut file = Vec::new();
+        write_index(&mut file, row_words, col_words, &index).unwrap();
+        assert!(read_index::<4, 4>(&mut &file[..]).is_err());
+    }
+}
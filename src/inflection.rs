@@ -0,0 +1,181 @@
+//! Spotting inflected forms such as plurals and -ed/-ing forms, so they can be
+//! dropped or given a lower frequency.
+//!
+//! A "lemma" column from `--columns` is used when there is one: a word is
+//! inflected if its lemma is given and differs from it. Otherwise the rough
+//! per-language suffix rules below are used, which will catch some words that
+//! merely look inflected ("string") and miss irregular forms ("mice").
+
+use std::io;
+
+use clap::{Arg, ArgMatches};
+
+use super::invalid_data;
+use super::columns::Schema;
+use super::text::TextOptions;
+
+/// Arguments read by `InflectionFilter::from_args`.
+pub fn inflection_args<'a, 'b>() -> Vec<Arg<'a, 'b>> {
+    vec![
+        Arg::with_name("inflections")
+            .long("inflections")
+            .takes_value(true)
+            .possible_values(&["keep", "exclude", "down-weight"])
+            .default_value("keep")
+            .help("What to do with inflected forms: keep them, drop them, or scale their freq column by --inflection-weight. Uses a \"lemma\" column in --columns if there is one, otherwise --inflection-rules."),
+        Arg::with_name("inflection-rules")
+            .long("inflection-rules")
+            .takes_value(true)
+            .possible_values(&["en", "es", "fr"])
+            .help("Language of the suffix rules used to spot inflected forms when there's no lemma column"),
+        Arg::with_name("inflection-weight")
+            .long("inflection-weight")
+            .takes_value(true)
+            .default_value("0.1")
+            .validator(|arg| {
+                match arg.parse::<f64>() {
+                    Ok(_) => Ok(()),
+                    Err(e) => Err(format!("Must provide a valid number. {:?}", e)),
+                }
+            })
+            .help("Factor to multiply the freq of inflected forms by with --inflections down-weight"),
+    ]
+}
+
+/// An inflectional ending, matched only if at least `min_stem` chars come
+/// before it and the char just before it isn't one of `not_after`.
+struct SuffixRule {
+    suffix: &'static str,
+    min_stem: usize,
+    not_after: &'static str,
+}
+
+const fn rule(suffix:&'static str, min_stem:usize, not_after:&'static str) -> SuffixRule {
+    SuffixRule{suffix, min_stem, not_after}
+}
+
+const EN_RULES:&[SuffixRule] = &[
+    rule("ies", 2, ""),
+    rule("s", 3, "siu'"),
+    rule("ed", 3, "e"),
+    rule("ing", 3, ""),
+];
+
+const ES_RULES:&[SuffixRule] = &[
+    rule("es", 3, ""),
+    rule("s", 3, "s"),
+    rule("ando", 2, ""),
+    rule("iendo", 2, ""),
+    rule("ado", 3, ""),
+    rule("ido", 3, ""),
+];
+
+const FR_RULES:&[SuffixRule] = &[
+    rule("s", 3, "su"),
+    rule("x", 3, ""),
+    rule("ées", 2, ""),
+    rule("ée", 2, ""),
+    rule("és", 2, ""),
+];
+
+#[derive(Clone,Copy)]
+enum Action {
+    Exclude,
+    DownWeight(f64),
+}
+
+#[derive(Clone,Copy)]
+enum Detection {
+    /// Metadata column index of the lemma.
+    Lemma(usize),
+    Rules(&'static [SuffixRule]),
+}
+
+pub struct InflectionFilter {
+    rules: Option<(Action, Detection)>,
+    text: TextOptions,
+    /// Metadata column index of the freq, for down-weighting.
+    freq_col: Option<usize>,
+    excluded: u64,
+    down_weighted: u64,
+}
+
+fn matches_rules(word:&str, rules:&[SuffixRule]) -> bool {
+    rules.iter().any(|rule| {
+        if !word.ends_with(rule.suffix) {
+            return false;
+        }
+        let stem:Vec<char> = word[..word.len() - rule.suffix.len()].chars().collect();
+        stem.len() >= rule.min_stem && stem.last().is_some_and(|c| !rule.not_after.contains(*c))
+    })
+}
+
+impl InflectionFilter {
+    /// Lemmas are converted with `text` so they compare equal to converted words.
+    pub fn from_args(args:&ArgMatches, text:&TextOptions, schema:Option<&Schema>) -> io::Result<InflectionFilter> {
+        let freq_col = schema.and_then(|s| s.meta_index("freq"));
+        let action = match args.value_of("inflections").unwrap() {
+            "exclude" => Action::Exclude,
+            "down-weight" => {
+                if freq_col.is_none() {
+                    return Err(invalid_data("--inflections down-weight needs a \"freq\" column in --columns"));
+                }
+                Action::DownWeight(args.value_of("inflection-weight").unwrap().parse().unwrap())
+            },
+            _ => return Ok(InflectionFilter{rules: None, text: *text, freq_col, excluded: 0, down_weighted: 0}),
+        };
+        let detection = match (schema.and_then(|s| s.meta_index("lemma")), args.value_of("inflection-rules")) {
+            (Some(col), _) => Detection::Lemma(col),
+            (None, Some(lang)) => Detection::Rules(match lang {
+                "es" => ES_RULES,
+                "fr" => FR_RULES,
+                _ => EN_RULES,
+            }),
+            (None, None) => return Err(invalid_data("--inflections needs a \"lemma\" column in --columns, or --inflection-rules")),
+        };
+        Ok(InflectionFilter{rules: Some((action, detection)), text: *text, freq_col, excluded: 0, down_weighted: 0})
+    }
+
+    fn is_inflected(&self, detection:Detection, word:&str, meta:&[String]) -> bool {
+        match detection {
+            Detection::Lemma(col) => meta.get(col).is_some_and(|lemma| {
+                let lemma = self.text.apply(lemma.trim());
+                !lemma.is_empty() && lemma != word
+            }),
+            Detection::Rules(rules) => matches_rules(word, rules),
+        }
+    }
+
+    /// Gives a reason if `word` should be dropped as inflected. Words that are
+    /// down-weighted instead have the freq value in `meta` scaled.
+    pub fn check(&mut self, word:&str, meta:&mut [String]) -> Option<&'static str> {
+        let (action, detection) = self.rules?;
+        if !self.is_inflected(detection, word, meta) {
+            return None;
+        }
+        match action {
+            Action::Exclude => {
+                self.excluded += 1;
+                Some("inflected")
+            },
+            Action::DownWeight(weight) => {
+                if let Some(freq) = self.freq_col.and_then(|col| meta.get_mut(col)) {
+                    let value = freq.trim().parse::<f64>().unwrap_or(0.0) * weight;
+                    *freq = format!("{}", (value * 1e6).round() / 1e6);
+                }
+                self.down_weighted += 1;
+                None
+            },
+        }
+    }
+
+    /// Prints how many words were dropped or down-weighted to STDERR.
+    pub fn report(&self) {
+        if self.excluded > 0 {
+            eprintln!("Filtered out {} inflected words.", self.excluded);
+        }
+        if self.down_weighted > 0 {
+            eprintln!("Down-weighted the freq of {} inflected words.", self.down_weighted);
+        }
+    }
+}
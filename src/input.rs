@@ -0,0 +1,56 @@
+//! Opening wordlists, which may be compressed.
+
+use std::fs::File;
+use std::io::{self, BufReader};
+use std::io::prelude::*;
+
+use bzip2::bufread::MultiBzDecoder;
+use flate2::bufread::MultiGzDecoder;
+use xz2::bufread::XzDecoder;
+
+#[derive(Debug,Clone,Copy,PartialEq,Eq)]
+enum Compression {
+    None,
+    Gzip,
+    Bzip2,
+    Xz,
+    Zstd,
+}
+
+impl Compression {
+    fn detect(start:&[u8]) -> Compression {
+        if start.starts_with(&[0x1f, 0x8b]) {
+            Compression::Gzip
+        } else if start.starts_with(b"BZh") {
+            Compression::Bzip2
+        } else if start.starts_with(&[0xfd, b'7', b'z', b'X', b'Z', 0x00]) {
+            Compression::Xz
+        } else if start.starts_with(&[0x28, 0xb5, 0x2f, 0xfd]) {
+            Compression::Zstd
+        } else {
+            Compression::None
+        }
+    }
+}
+
+/// Wraps `input` in a decompressor if it starts with the magic bytes of a
+/// gzip, bzip2, xz or zstd stream; otherwise passes it through untouched.
+pub fn decompress<'a>(mut input:impl BufRead + 'a) -> io::Result<Box<dyn BufRead + 'a>> {
+    let compression = Compression::detect(input.fill_buf()?);
+    Ok(match compression {
+        Compression::None => Box::new(input),
+        Compression::Gzip => Box::new(BufReader::new(MultiGzDecoder::new(input))),
+        Compression::Bzip2 => Box::new(BufReader::new(MultiBzDecoder::new(input))),
+        Compression::Xz => Box::new(BufReader::new(XzDecoder::new_multi_decoder(input))),
+        Compression::Zstd => Box::new(BufReader::new(zstd::stream::read::Decoder::with_buffer(input)?)),
+    })
+}
+
+/// Opens the file at `path`, or stdin for "-", transparently decompressing it.
+pub fn open_input(path:&str) -> io::Result<Box<dyn BufRead>> {
+    if path == "-" {
+        decompress(BufReader::new(io::stdin()))
+    } else {
+        decompress(BufReader::new(File::open(path)?))
+    }
+}
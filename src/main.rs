@@ -2,32 +2,81 @@
 #![feature(nll)]
 
 extern crate fnv;
-extern crate spmc;
+extern crate crossbeam_deque;
 extern crate unicode_skeleton;
+extern crate unicode_normalization;
+extern crate caseless;
+extern crate regex;
 #[macro_use]
 extern crate clap;
+#[macro_use]
+extern crate serde_json;
+extern crate flate2;
+extern crate bzip2;
+extern crate xz2;
+extern crate zstd;
+
+mod alphabet;
+mod bench;
+mod columns;
+mod combine;
+mod estimate;
+mod filter;
+mod hunspell;
+mod index_file;
+mod inflection;
+mod input;
+mod memory;
+mod phrase;
+mod results;
+mod rng;
+mod scheduler;
+mod search_stats;
+mod stats;
+mod summary;
+mod text;
+mod wiktextract;
+mod wordlist_writer;
 
 use std::default::Default;
 use std::vec::Vec;
 use std::io::{self, BufReader, BufWriter};
 use std::io::prelude::*;
 use std::fs::File;
+use std::mem;
+use std::path::{Path, PathBuf};
 use std::thread;
 
-use fnv::FnvHashMap;
+use fnv::{FnvHashMap, FnvHashSet};
 
 use unicode_skeleton::UnicodeSkeleton;
 
 use clap::{Arg, App, SubCommand, ArgMatches};
 
+use bench::bench_size;
+use estimate::{estimate_size, EstimateOptions};
+use columns::{Schema, WordMetadata};
+use filter::WordFilter;
+use inflection::InflectionFilter;
+use input::open_input;
+use memory::{format_bytes, map_bytes};
+use phrase::PhraseOptions;
+use results::{OutputFormat, ResultWriter};
+use scheduler::{Donate, DONATE_CHECK_INTERVAL};
+use search_stats::{DepthStats, NoStats, SearchStats};
+use stats::PreprocessStats;
+use summary::{SizeSummary, SummaryFormat};
+use text::TextOptions;
+use wordlist_writer::WordlistWriter;
+
 macro_rules! make_encode_decode {
     (
-        $( $num:expr => $char:expr; )+
+        $( $(#[$attr:meta])* $num:literal => $char:literal; )+
     ) => {
         fn encode(from:char) -> Option<u8> {
             let res:u8 = match from {
                 $(
-                    $char => $num,
+                    $(#[$attr])* $char => $num,
                 )+
                 _ => return None,
             };
@@ -37,10 +86,10 @@ macro_rules! make_encode_decode {
         fn decode(code:u8) -> Option<char> {
             let res:char = match code {
                 $(
-                    $num => $char,
+                    $(#[$attr])* $num => $char,
                 )+
                 255 => '#',
-                32 => '$',
+                c if c == ALPHABET_SIZE => '$',
                 _ => return None,
             };
             return Some(res)
@@ -81,21 +130,46 @@ make_encode_decode!{
     29 => 'é';
     30 => 'm';
     31 => 'q';
+    #[cfg(feature = "phrases")]
+    32 => ' ';
+}
+
+/// Number of codes for letters in the alphabet above. analyze-alphabet prints
+/// it along with the alphabet, as the two have to match.
+const LETTER_CODES:u8 = 32;
+
+/// Number of codes in the alphabet above; the code equal to this is used as a sentinel.
+const ALPHABET_SIZE:u8 = LETTER_CODES + cfg!(feature = "phrases") as u8;
+
+/// Bits of a `CharSet`, one per code. Only builds with the phrases feature
+/// need more than 32, so others keep index entries small.
+#[cfg(feature = "phrases")]
+type CharSetBits = u64;
+#[cfg(not(feature = "phrases"))]
+type CharSetBits = u32;
+
+/// Every encodable char, in code order.
+fn alphabet() -> Vec<char> {
+    (0..ALPHABET_SIZE).map(|c| decode(c).unwrap()).collect()
+}
+
+fn invalid_data(msg:&str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg.to_string())
 }
 
 #[derive(Debug,Clone,Copy,PartialEq,Eq)]
 struct CharSet {
-    pub internal:u32
+    pub internal:CharSetBits
 }
 
 impl CharSet {
-    fn new(internal:u32) -> CharSet {
+    fn new(internal:CharSetBits) -> CharSet {
         return CharSet{internal}
     }
 
     fn add(&mut self, val:u8) {
-        if val > 31 {panic!("Invalid val {}", val)}
-        self.internal |= 2u32.pow(val as u32)
+        if val >= ALPHABET_SIZE {panic!("Invalid val {}", val)}
+        self.internal |= (2 as CharSetBits).pow(val as u32)
     }
 
     fn and(&self, other:&Self) -> Self {
@@ -103,10 +177,10 @@ impl CharSet {
     }
 
     fn has(&self, val:u8) -> bool {
-        if val > 31 {
+        if val >= ALPHABET_SIZE {
             panic!("Invalid val {}", val)
         } else {
-            return (self.internal & 2u32.pow(val as u32)) > 0
+            return (self.internal & (2 as CharSetBits).pow(val as u32)) > 0
         }
     }
 }
@@ -185,54 +259,162 @@ const WORD_SQUARE_HEIGHT:usize = 15;
 
 const WORD_SQUARE_SIZE:usize = WORD_SQUARE_WIDTH * WORD_SQUARE_HEIGHT;
 
-type WideWord = [u8; WORD_SQUARE_WIDTH];
-type TallWord = [u8; WORD_SQUARE_HEIGHT];
-type WordSquare = [u8; WORD_SQUARE_SIZE];
+/// Sizes `compute --sizes` can run, besides the one this binary was built for.
+const MIN_SIZE:usize = 2;
+const MAX_SIZE:usize = 15;
+
+/// Prefix sets for a `W` by `H` grid. Code that depends on the grid size is
+/// generic over `W` and `H` (and `S`, always `W * H`, the number of cells),
+/// so one binary can search several sizes; the size chosen with the width and
+/// height features is the one used unless `--sizes` is given.
+#[derive(Debug)]
+struct WordIndex<const W:usize, const H:usize> {
+    inner_rows: FnvHashMap<[u8; W],CharSet>,
+    /// Square grids share the rows map for columns unless separate across and
+    /// down lists were given.
+    inner_cols: Option<FnvHashMap<[u8; H],CharSet>>,
+    /// Extra columns of indexed words, if the wordlist had any.
+    meta: WordMetadata,
+}
 
-#[derive(Debug,Default)]
-struct WordIndex {
-    inner_rows: FnvHashMap<WideWord,CharSet>,
-    #[cfg(not(feature = "square"))]
-    inner_cols: FnvHashMap<TallWord,CharSet>,
+impl<const W:usize, const H:usize> Default for WordIndex<W, H> {
+    fn default() -> Self {
+        WordIndex{
+            inner_rows: FnvHashMap::default(),
+            inner_cols: if W == H { None } else { Some(FnvHashMap::default()) },
+            meta: WordMetadata::default(),
+        }
+    }
 }
 
-impl WordIndex {
-    fn rows(&self) -> &FnvHashMap<WideWord,CharSet> {
+impl<const W:usize, const H:usize> WordIndex<W, H> {
+    fn rows(&self) -> &FnvHashMap<[u8; W],CharSet> {
         &self.inner_rows
     }
 
-    fn cols(&self) -> &FnvHashMap<TallWord,CharSet> {
-        #[cfg(not(feature = "square"))]
-        return &self.inner_cols;
-        #[cfg(feature = "square")]
-        return self.rows();
+    /// The columns map, or None if columns share the rows map.
+    fn separate_cols(&self) -> Option<&FnvHashMap<[u8; H],CharSet>> {
+        self.inner_cols.as_ref()
     }
 
-    fn rows_mut(&mut self) -> &mut FnvHashMap<WideWord,CharSet> {
+    fn rows_mut(&mut self) -> &mut FnvHashMap<[u8; W],CharSet> {
         &mut self.inner_rows
     }
 
-    #[cfg(not(feature = "square"))]
-    fn cols_mut(&mut self) -> &mut FnvHashMap<TallWord,CharSet> {
-        &mut self.inner_cols
+    fn cols_mut(&mut self) -> &mut FnvHashMap<[u8; H],CharSet> {
+        self.inner_cols.get_or_insert_with(Default::default)
+    }
+
+    /// Whether columns have their own map rather than sharing the rows map.
+    fn has_separate_cols(&self) -> bool {
+        self.inner_cols.is_some()
+    }
+
+    fn is_empty(&self) -> bool {
+        self.inner_rows.is_empty() || self.inner_cols.as_ref().is_some_and(|cols| cols.is_empty())
+    }
+
+    /// Approximate bytes taken by the rows map.
+    fn row_bytes(&self) -> u64 {
+        map_bytes(self.inner_rows.capacity(), mem::size_of::<([u8; W], CharSet)>())
+    }
+
+    /// Approximate bytes taken by the columns map, or 0 if it's shared.
+    fn col_bytes(&self) -> u64 {
+        self.inner_cols.as_ref().map_or(0, |cols| map_bytes(cols.capacity(), mem::size_of::<([u8; H], CharSet)>()))
     }
-}
 
-fn print_word_square(sq:WordSquare){
-    let mut first = true;
-    for i in 0..WORD_SQUARE_HEIGHT {
-        let mut chars = Vec::new();
-        for j in 0..WORD_SQUARE_WIDTH {
-            chars.push(decode(sq[i*WORD_SQUARE_WIDTH + j]).unwrap());
+    /// Describes the entries and memory of each map, for loud output.
+    fn usage(&self) -> String {
+        match self.inner_cols {
+            Some(ref cols) => format!(
+                "{} row prefixes (~{}), {} column prefixes (~{}), ~{} in all",
+                self.inner_rows.len(), format_bytes(self.row_bytes()),
+                cols.len(), format_bytes(self.col_bytes()),
+                format_bytes(self.row_bytes() + self.col_bytes()),
+            ),
+            None => format!(
+                "{} prefixes shared by rows and columns (~{})",
+                self.inner_rows.len(), format_bytes(self.row_bytes()),
+            ),
         }
-        let word = chars.iter().collect::<String>();
-        if !first {
-            print!("-");
+    }
+
+    fn row_set(&self, prefix:&[u8; W]) -> CharSet {
+        self.inner_rows[prefix]
+    }
+
+    fn col_set(&self, prefix:&[u8; H]) -> CharSet {
+        match self.inner_cols {
+            Some(ref cols) => cols[prefix],
+            // Only possible when W == H; arrays hash the same as slices.
+            None => self.inner_rows[&prefix[..]],
         }
-        print!("{}", word);
-        first = false;
     }
-    println!();
+}
+
+/// Which parts of the index a wordlist's words are added to.
+#[derive(Debug,Clone,Copy,PartialEq,Eq)]
+enum Orientation {
+    Rows,
+    Cols,
+    Both,
+}
+
+/// Arguments that only affect reading and indexing a wordlist, so can't be
+/// used with `--index`.
+const WORDLIST_ONLY_ARGS:&[&str] = &[
+    "wordlist", "row-words", "col-words", "ignore-unencodeable",
+    "normalize", "case-fold", "accents",
+    "phrases", "phrase-separator",
+    "columns",
+    "min-len", "max-len", "include-regex", "exclude-regex", "blocklist", "allowlist", "min-freq", "pos",
+    "inflections", "inflection-rules", "inflection-weight",
+    "max-memory",
+];
+
+/// The `--threads` argument of the subcommands that search.
+fn threads_arg<'a, 'b>() -> Arg<'a, 'b> {
+    Arg::with_name("threads")
+        .takes_value(true)
+        .validator(|arg| {
+            match arg.parse::<u32>() {
+                Ok(_) => Ok(()),
+                Err(e) => Err(format!("Must provide a valid integer. {:?}", e)),
+            }
+        })
+        .help("Number of threads to use, with 0 meaning the number of CPUs available to this process, which takes cgroup CPU quotas into account. Defaults to that too, except for bench, which defaults to 1 for steadier timings.")
+        .long("threads")
+        .short("t")
+}
+
+/// Arguments for reading a wordlist, shared by every subcommand that does.
+/// If `required`, a wordlist, `--row-words`, `--col-words` or, where the
+/// subcommand has it, `--index` must be given.
+fn wordlist_args<'a, 'b>(required:bool) -> Vec<Arg<'a, 'b>> {
+    let mut wordlist = Arg::with_name("wordlist");
+    if required {
+        wordlist = wordlist.required_unless_one(&["row-words", "col-words", "index"]);
+    }
+    vec![
+        wordlist
+            .help("the wordlist file path, a plain-text UTF-8 file with each word separated by a newline. May be compressed with gzip, bzip2, xz or zstd."),
+        Arg::with_name("row-words")
+            .long("row-words")
+            .takes_value(true)
+            .help("Wordlist for the rows (across words), instead of the main wordlist"),
+        Arg::with_name("col-words")
+            .long("col-words")
+            .takes_value(true)
+            .help("Wordlist for the columns (down words), instead of the main wordlist"),
+        Arg::with_name("ignore-unencodeable")
+            .long("ignore-unencodeable")
+            .help("Don't show a warning when a word is dropped because it contains unencodeable characters."),
+        Arg::with_name("quiet")
+            .long("quiet")
+            .short("q")
+            .help("Don't show any status messages; STDERR will be empty if no errors occured."),
+    ]
 }
 
 fn main() -> io::Result<()> {
@@ -243,35 +425,264 @@ fn main() -> io::Result<()> {
         .setting(clap::AppSettings::SubcommandRequired)
         .subcommand(SubCommand::with_name("compute")
             .about("Does the actual computation.")
-            .arg(Arg::with_name("threads")
-                .default_value("4")
+            .arg(threads_arg())
+            .args(&wordlist_args(true))
+            .arg(Arg::with_name("split-depth")
+                .long("split-depth")
                 .takes_value(true)
+                .default_value("auto")
                 .validator(|arg| {
-                    match arg.parse::<u32>() {
+                    if arg == "auto" {
+                        return Ok(());
+                    }
+                    match arg.parse::<usize>() {
                         Ok(_) => Ok(()),
-                        Err(e) => Err(String::from(format!("Must provide a valid integer. {:?}", e))),
+                        Err(e) => Err(format!("Must be \"auto\" or a valid integer. {:?}", e)),
                     }
                 })
-                .help("Number of threads to use.")
-                .long("threads")
-                .short("t")
+                .help("How many cells to fill in before handing the search out to threads, or \"auto\" to go deep enough for a few dozen tasks per thread. Threads still split big tasks between themselves as they run.")
             )
-            .arg(Arg::with_name("wordlist")
-                .required(true)
-                .help("the wordlist file path, a plain-text UTF-8 file with each word separated by a newline")
+            .arg(Arg::with_name("index")
+                .long("index")
+                .short("i")
+                .takes_value(true)
+                .conflicts_with_all(WORDLIST_ONLY_ARGS)
+                .help("Load an index written by build-index instead of reading a wordlist")
+            )
+            .arg(Arg::with_name("sizes")
+                .long("sizes")
+                .takes_value(true)
+                .conflicts_with("index")
+                .requires("output-dir")
+                .validator(|arg| parse_sizes(&arg).map(|_| ()))
+                .help("Search several sizes in one run, reading the wordlist once, eg \"5x5,8x5\" or \"2x2..15x15\". Sizes can't be taller than they are wide, and a range skips those that would be. Sizes other than this binary's own need the sweep feature.")
+            )
+            .arg(Arg::with_name("output-dir")
+                .long("output-dir")
+                .takes_value(true)
+                .requires("sizes")
+                .help("With --sizes, the directory to write each size's squares to, as eg 8x5.txt")
+            )
+            .arg(Arg::with_name("size-jobs")
+                .long("size-jobs")
+                .takes_value(true)
+                .default_value("1")
+                .validator(|arg| {
+                    match arg.parse::<usize>() {
+                        Ok(n) if n >= 1 => Ok(()),
+                        Ok(_) => Err(String::from("Must be at least 1")),
+                        Err(e) => Err(format!("Must provide a valid integer. {:?}", e)),
+                    }
+                })
+                .help("With --sizes, how many sizes to search at once, each with --threads worker threads")
             )
             .arg(Arg::with_name("ignore-empty-wordlist")
                 .long("ignore-empty-wordlist")
                 .help("Don't complain if there are no words of the necessary length in the given wordlist")
             )
-            .arg(Arg::with_name("ignore-unencodeable")
-                .long("ignore-unencodeable")
-                .help("Don't show a warning when a word is dropped because it contains unencodeable characters.")
+            .args(&text::text_args())
+            .args(&phrase::phrase_args())
+            .args(&columns::column_args())
+            .args(&filter::filter_args())
+            .args(&inflection::inflection_args())
+            .args(&memory::memory_args())
+            .args(&summary::summary_args())
+            .arg(Arg::with_name("search-stats")
+                .long("search-stats")
+                .help("Count the nodes, dead ends, backtracks and squares at each cell of the search and print them as a table to STDERR at the end. Slows the search down a little.")
+            )
+            .arg(Arg::with_name("format")
+                .long("format")
+                .short("f")
+                .takes_value(true)
+                .possible_values(&["text", "binary", "jsonl"])
+                .default_value("text")
+                .help("Output format for found squares. \"binary\" packs each square into a few bytes; convert it back with decode-results.")
+            )
+        )
+        .subcommand(SubCommand::with_name("bench")
+            .about("Times building the index and searching every square for several sizes, reporting nodes and squares per second. Uses generated words unless a wordlist is given; squares are counted but not written.")
+            .arg(Arg::with_name("sizes")
+                .long("sizes")
+                .takes_value(true)
+                .validator(|arg| parse_sizes(&arg).map(|_| ()))
+                .help("Sizes to run, eg \"5x5,8x5\" or \"2x2..6x6\", as for compute --sizes. Defaults to 3x3,4x4,5x4,5x5,6x5 with the sweep feature, or else the size this binary was built for.")
+            )
+            .arg(threads_arg())
+            .args(&wordlist_args(false))
+            .args(&bench::bench_args())
+            .arg(Arg::with_name("format")
+                .long("format")
+                .short("f")
+                .takes_value(true)
+                .possible_values(&["markdown", "csv", "json"])
+                .default_value("markdown")
+                .help("Format of the results table, written to STDOUT")
+            )
+            .args(&text::text_args())
+            .args(&phrase::phrase_args())
+            .args(&columns::column_args())
+            .args(&filter::filter_args())
+            .args(&inflection::inflection_args())
+            .args(&memory::memory_args())
+        )
+        .subcommand(SubCommand::with_name("estimate")
+            .about("Predicts how many nodes a search will explore, how many squares it will find and how long it will take, with 95% confidence intervals, by randomly probing the search tree instead of searching all of it.")
+            .arg(Arg::with_name("sizes")
+                .long("sizes")
+                .takes_value(true)
+                .conflicts_with("index")
+                .validator(|arg| parse_sizes(&arg).map(|_| ()))
+                .help("Sizes to estimate, eg \"5x5,8x5\" or \"2x2..15x15\", as for compute --sizes. Defaults to the size this binary was built for.")
+            )
+            .arg(threads_arg())
+            .args(&wordlist_args(true))
+            .args(&estimate::estimate_args())
+            .arg(Arg::with_name("format")
+                .long("format")
+                .short("f")
+                .takes_value(true)
+                .possible_values(&["markdown", "csv", "json"])
+                .default_value("markdown")
+                .help("Format of the estimates table, written to STDOUT")
+            )
+            .arg(Arg::with_name("index")
+                .long("index")
+                .short("i")
+                .takes_value(true)
+                .conflicts_with_all(WORDLIST_ONLY_ARGS)
+                .help("Load an index written by build-index instead of reading a wordlist")
+            )
+            .args(&text::text_args())
+            .args(&phrase::phrase_args())
+            .args(&columns::column_args())
+            .args(&filter::filter_args())
+            .args(&inflection::inflection_args())
+            .args(&memory::memory_args())
+        )
+        .subcommand(SubCommand::with_name("build-index")
+            .about("Reads a wordlist and saves the index compute builds from it, for use with `compute --index`. The index only works with a binary of the same size.")
+            .args(&wordlist_args(true))
+            .args(&text::text_args())
+            .args(&phrase::phrase_args())
+            .args(&columns::column_args())
+            .args(&filter::filter_args())
+            .args(&inflection::inflection_args())
+            .args(&memory::memory_args())
+            .arg(Arg::with_name("index-filename")
+                .long("output")
+                .short("o")
+                .takes_value(true)
+                .required(true)
+                .help("The path to write the index to")
+            )
+        )
+        .subcommand(SubCommand::with_name("decode-results")
+            .about("Converts results written with `compute --format binary` back into text or JSONL.")
+            .arg(Arg::with_name("format")
+                .long("format")
+                .short("f")
+                .takes_value(true)
+                .possible_values(&["text", "jsonl"])
+                .default_value("text")
+                .help("Output format")
+            )
+            .arg(Arg::with_name("input-filename")
+                .required(true)
+                .help("The path to the binary results file, or \"-\" for stdin")
+            )
+        )
+        .subcommand(SubCommand::with_name("analyze-alphabet")
+            .about("Counts how often each character appears in a wordlist and prints an alphabet for make_encode_decode! ordered by frequency with the LETTER_CODES to match, flagging characters that don't fit in the available codes.")
+            .args(&text::text_args())
+            .arg(Arg::with_name("lengths")
+                .long("lengths")
+                .short("l")
+                .takes_value(true)
+                .multiple(true)
+                .require_delimiter(true)
+                .validator(|arg| {
+                    match arg.parse::<usize>() {
+                        Ok(_) => Ok(()),
+                        Err(e) => Err(format!("Must provide a valid integer. {:?}", e)),
+                    }
+                })
+                .help("Only count words of these lengths, eg \"5,8\" for 8x5 rectangles")
+            )
+            .arg(Arg::with_name("format")
+                .long("format")
+                .short("f")
+                .takes_value(true)
+                .possible_values(&["macro", "json"])
+                .default_value("macro")
+                .help("Output format")
+            )
+            .arg(Arg::with_name("wordlist")
+                .required(true)
+                .help("the wordlist file path, a plain-text UTF-8 file with each word separated by a newline, or \"-\" for stdin. May be compressed with gzip, bzip2, xz or zstd.")
+            )
+        )
+        .subcommand(SubCommand::with_name("wordlist-combine")
+            .about("Makes compound words by joining words from a wordlist, optionally with a separator, keeping only compounds of lengths a grid can use.")
+            .arg(Arg::with_name("separator")
+                .long("separator")
+                .short("s")
+                .takes_value(true)
+                .default_value("")
+                .help("Put this between the joined words")
+            )
+            .arg(Arg::with_name("words")
+                .long("words")
+                .short("k")
+                .takes_value(true)
+                .default_value("2")
+                .validator(|arg| {
+                    match arg.parse::<usize>() {
+                        Ok(n) if n >= 1 => Ok(()),
+                        Ok(_) => Err(String::from("Must be at least 1")),
+                        Err(e) => Err(format!("Must provide a valid integer. {:?}", e)),
+                    }
+                })
+                .help("How many words to join into each compound")
+            )
+            .arg(Arg::with_name("lengths")
+                .long("lengths")
+                .short("l")
+                .takes_value(true)
+                .multiple(true)
+                .require_delimiter(true)
+                .validator(|arg| {
+                    match arg.parse::<usize>() {
+                        Ok(_) => Ok(()),
+                        Err(e) => Err(format!("Must provide a valid integer. {:?}", e)),
+                    }
+                })
+                .help("Only output compounds with these total lengths, including separators. Defaults to the width and height of this binary.")
             )
-            .arg(Arg::with_name("quiet")
-                .long("quiet")
-                .short("q")
-                .help("Don't show any status messages; STDERR will be empty if no errors occured.")
+            .arg(Arg::with_name("min-word-len")
+                .long("min-word-len")
+                .takes_value(true)
+                .default_value("2")
+                .validator(|arg| {
+                    match arg.parse::<usize>() {
+                        Ok(_) => Ok(()),
+                        Err(e) => Err(format!("Must provide a valid integer. {:?}", e)),
+                    }
+                })
+                .help("Don't use words shorter than this")
+            )
+            .arg(Arg::with_name("unordered")
+                .long("unordered")
+                .short("u")
+                .help("Output each combination of words once, shortest words first, instead of every ordering of it")
+            )
+            .arg(Arg::with_name("input-filename")
+                .required(true)
+                .help("The path to the wordlist to read from, or \"-\" for stdin. May be compressed with gzip, bzip2, xz or zstd.")
+            )
+            .arg(Arg::with_name("output-filename")
+                .required(true)
+                .help("The path to write compounds to, or \"-\" for stdout")
             )
         )
         .subcommand(SubCommand::with_name("wordlist-preprocess")
@@ -288,13 +699,70 @@ fn main() -> io::Result<()> {
                 .long_help("Input wordlist is a plaintext UTF-8 newline-separated list of words")
                 .group("format")
             )
+            .arg(Arg::with_name("hunspell")
+                .long("hunspell")
+                .takes_value(true)
+                .value_name("AFF-FILE")
+                .long_help("Input wordlist is a Hunspell .dic file, and this is the path of its .aff file. Affix rules are expanded so every word form is output, e.g. `--hunspell en_US.aff en_US.dic out.txt`")
+                .group("format")
+            )
+            .arg(Arg::with_name("wiktextract-format")
+                .long("wiktextract")
+                .short("j")
+                .long_help("Input wordlist is a wiktextract JSONL dump, with one JSON entry per line. See --lang, --pos and --exclude-tags for choosing which entries to keep.")
+                .group("format")
+            )
+            .arg(Arg::with_name("lang")
+                .long("lang")
+                .takes_value(true)
+                .requires("wiktextract-format")
+                .help("Only keep wiktextract entries with this language code, eg \"en\"")
+            )
+            .arg(Arg::with_name("exclude-tags")
+                .long("exclude-tags")
+                .takes_value(true)
+                .multiple(true)
+                .require_delimiter(true)
+                .default_value("abbreviation,obsolete,proper noun")
+                .help("Drop wiktextract entries with any of these tags, or whose senses all have one. \"proper noun\" matches entries whose part of speech is a name. Use `--exclude-tags=` to keep everything.")
+            )
+            .args(&text::text_args())
+            .args(&phrase::phrase_args())
+            .args(&columns::column_args())
+            .args(&filter::filter_args())
+            .args(&inflection::inflection_args())
+            .arg(Arg::with_name("dedupe")
+                .long("dedupe")
+                .short("d")
+                .help("Only output the first occurrence of each word, after characters have been converted")
+            )
+            .arg(Arg::with_name("sort")
+                .long("sort")
+                .short("s")
+                .help("Sort the output words. This holds every word in memory until the input has been read.")
+            )
+            .arg(Arg::with_name("split-by-length")
+                .long("split-by-length")
+                .help("Treat the output path as a directory, and write words of each length N to words-N.txt inside it")
+            )
+            .arg(Arg::with_name("stats")
+                .long("stats")
+                .help("Print a report to STDERR of how many words were kept and why others were dropped, kept word lengths, and the most frequent unencodable characters")
+            )
+            .arg(Arg::with_name("stats-format")
+                .long("stats-format")
+                .takes_value(true)
+                .possible_values(&["table", "json"])
+                .default_value("table")
+                .help("Format of the --stats report")
+            )
             .arg(Arg::with_name("input-filename")
                 .required(true)
-                .help("The path to the wordlist to read from, or \"-\" for stdin")
+                .help("The path to the wordlist to read from, or \"-\" for stdin. May be compressed with gzip, bzip2, xz or zstd.")
             )
             .arg(Arg::with_name("output-filename")
                 .required(true)
-                .help("The path to the wordlist to write to, or \"-\" for stdout")
+                .help("The path to the wordlist to write to, or \"-\" for stdout. With --split-by-length, the directory to write to.")
             )
         ).get_matches();
     
@@ -303,6 +771,12 @@ fn main() -> io::Result<()> {
     return match matches.subcommand() {
         ("compute", Some(m)) => compute_command(m),
         ("wordlist-preprocess", Some(m)) => wordlist_preprocess(m),
+        ("decode-results", Some(m)) => decode_results_command(m),
+        ("build-index", Some(m)) => build_index_command(m),
+        ("bench", Some(m)) => bench_command(m),
+        ("estimate", Some(m)) => estimate_command(m),
+        ("analyze-alphabet", Some(m)) => analyze_alphabet_command(m),
+        ("wordlist-combine", Some(m)) => wordlist_combine(m),
         _ => panic!("This shouldn't happen"),
     }
     /*let mut args:Vec<String> = std::env::args().collect();
@@ -324,88 +798,294 @@ fn main() -> io::Result<()> {
     }*/
 }
 
-fn filter_word(word:&str) -> Option<String> {
-    let mut success = true;
-    let res = Some(word.chars().map(|c| {
+/// Maps each char of `word` to an encodable one, or gives the chars that couldn't be.
+fn filter_word(word:&str) -> Result<String,Vec<char>> {
+    let mut unencodable = Vec::new();
+    let res = word.chars().map(|c| {
         match encode(c) {
             Some(_) => c,
             None => {
                 let chars:Vec<char> = c.to_string().skeleton_chars().collect();
                 if chars.len() != 1 {
-                    success = false;
+                    unencodable.push(c);
                     'a'
                 } else {
                     match encode(chars[0]) {
                         Some(_) => chars[0],
-                        None => {success = false; 'a'},
+                        None => {unencodable.push(c); 'a'},
                     }
                 }
             },
         }
-    }).collect::<String>());
-    if success {
-        return res
+    }).collect::<String>();
+    if unencodable.is_empty() {
+        return Ok(res)
     } else {
-        return None
+        return Err(unencodable)
     }
 }
 
-fn wordlist_preprocess(args:&ArgMatches) -> io::Result<()> {
-
-    let in_file =  File::open(  args.value_of("input-filename" ).unwrap())?;
-    let out_file = File::create(args.value_of("output-filename").unwrap())?;
-
-    let wik_format = args.is_present("wiktionary-list-format");
-    
-    let f = BufReader::new(in_file);
-    let mut fo = BufWriter::new(out_file);
+/// Words from the input of wordlist-preprocess, read according to the chosen format.
+fn preprocess_input_words<'a>(
+    args:&ArgMatches,
+    f:Box<dyn BufRead + 'a>,
+) -> io::Result<Box<dyn Iterator<Item = io::Result<String>> + 'a>> {
+    if let Some(aff_path) = args.value_of("hunspell") {
+        let affixes = hunspell::Affixes::parse(open_input(aff_path)?)?;
+        return Ok(Box::new(affixes.expand_dic(f)));
+    }
     let mut lines = f.lines();
-    if wik_format {
-        //Skip the first line
-        lines.next().unwrap()?;
+    if args.is_present("wiktextract-format") {
+        let filter = wiktextract::EntryFilter{
+            lang: args.value_of("lang").map(String::from),
+            pos: args.values_of("pos").map(|v| v.map(String::from).collect()).unwrap_or_default(),
+            exclude_tags: args.values_of("exclude-tags").unwrap().map(String::from).collect(),
+        };
+        return Ok(Box::new(lines.filter_map(move |line_result| {
+            match line_result {
+                Ok(line) => filter.line_word(&line).transpose(),
+                Err(e) => Some(Err(e)),
+            }
+        })));
     }
-    for line_result in lines {
+    if !args.is_present("wiktionary-list-format") {
+        return Ok(Box::new(lines));
+    }
+    //Skip the first line
+    lines.next().unwrap()?;
+    Ok(Box::new(lines.map(|line_result| {
         let line = line_result?;
-        let word;
-        if wik_format {
-            let mut split = line.split('\t');
-            split.next().unwrap(); // skip before tab
-            word = split.next().unwrap();
-            match split.next() {
-                Some(_) => panic!("Only one tab expected per line"),
-                None => (),
-            }
+        let mut split = line.split('\t');
+        split.next().unwrap(); // skip before tab
+        let word = split.next().unwrap().to_string();
+        if split.next().is_some() {
+            panic!("Only one tab expected per line");
+        }
+        Ok(word)
+    })))
+}
+
+fn wordlist_preprocess(args:&ArgMatches) -> io::Result<()> {
+
+    let f = open_input(args.value_of("input-filename").unwrap())?;
+    let out_path = args.value_of("output-filename").unwrap();
+    let mut fo = if args.is_present("split-by-length") {
+        WordlistWriter::by_length(out_path)?
+    } else {
+        WordlistWriter::single(out_path)?
+    };
+
+    let text = TextOptions::from_args(args);
+    let phrases = PhraseOptions::from_args(args);
+    let schema = Schema::from_args(args)?;
+    let plain = !(args.is_present("hunspell") || args.is_present("wiktextract-format") || args.is_present("wiktionary-list-format"));
+    if schema.is_some() && !plain {
+        return Err(invalid_data("--columns only works with plain wordlists"));
+    }
+    let mut filter = WordFilter::from_args(args, &text, schema.as_ref())?;
+    let mut inflections = InflectionFilter::from_args(args, &text, schema.as_ref())?;
+    let dedupe = args.is_present("dedupe");
+    let sort = args.is_present("sort");
+    let mut stats = PreprocessStats::default();
+    let mut seen = FnvHashSet::default();
+    let mut sorted_words = Vec::new();
+
+    for word_result in preprocess_input_words(args, f)? {
+        let line = word_result?;
+        let (word, mut meta) = match schema {
+            Some(ref schema) => schema.split(&line),
+            None => (line, Vec::new()),
+        };
+        let word = match phrases.check(&text.apply(&word)) {
+            Ok(word) => word,
+            Err(reason) => {
+                stats.reject(reason);
+                continue;
+            },
+        };
+        let word = match filter_word(&word) {
+            Ok(word) => word,
+            Err(chars) => {
+                stats.reject("unencodable characters");
+                stats.unencodable(&chars);
+                continue;
+            },
+        };
+        if let Some(reason) = inflections.check(&word, &mut meta) {
+            stats.reject(reason);
+            continue;
+        }
+        if let Some(reason) = filter.check(&word, &meta) {
+            stats.reject(reason);
+            continue;
+        }
+        if dedupe && !seen.insert(word.clone()) {
+            stats.reject("duplicate");
+            continue;
+        }
+        stats.keep(&word);
+        // With --columns the metadata is written back out after the converted word.
+        let line = match schema {
+            Some(ref schema) => schema.join(&word, &meta),
+            None => word.clone(),
+        };
+        if sort {
+            sorted_words.push((word, line));
         } else {
-            word = &line
+            fo.write_line(&word, &line)?;
         }
-        match filter_word(word) {
-            Some(word) => writeln!(&mut fo, "{}", word)?,
-            None => (),
+    }
+    sorted_words.sort_unstable();
+    for (word, line) in sorted_words {
+        fo.write_line(&word, &line)?;
+    }
+    fo.finish()?;
+    filter.report();
+    inflections.report();
+
+    if args.is_present("stats") {
+        let stderr = io::stderr();
+        let mut err = stderr.lock();
+        match args.value_of("stats-format").unwrap() {
+            "json" => writeln!(err, "{}", stats.to_json())?,
+            _ => stats.write_table(&mut err)?,
         }
     }
-    fo.flush()?;
     return Ok(());
 }
 
-fn make_words_index(
-    f_in: impl BufRead,
+fn analyze_alphabet_command(args:&ArgMatches) -> io::Result<()> {
+    let text = TextOptions::from_args(args);
+    let lengths:Option<Vec<usize>> = args.values_of("lengths")
+        .map(|v| v.map(|l| l.parse().unwrap()).collect());
+
+    let mut counts = alphabet::AlphabetCounts::default();
+    for line_result in open_input(args.value_of("wordlist").unwrap())?.lines() {
+        let word = text.apply(&line_result?);
+        if word.is_empty() {
+            continue;
+        }
+        if let Some(ref lengths) = lengths {
+            if !lengths.contains(&word.chars().count()) {
+                continue;
+            }
+        }
+        counts.add_word(&word);
+    }
+
+    let stdout = io::stdout();
+    let mut out = stdout.lock();
+    match args.value_of("format").unwrap() {
+        "json" => writeln!(out, "{}", counts.to_json())?,
+        _ => counts.write_macro(&mut out)?,
+    }
+    Ok(())
+}
+
+fn wordlist_combine(args:&ArgMatches) -> io::Result<()> {
+    let opts = combine::CombineOptions{
+        words: args.value_of("words").unwrap().parse().unwrap(),
+        separator: args.value_of("separator").unwrap().to_string(),
+        lengths: match args.values_of("lengths") {
+            Some(lengths) => lengths.map(|l| l.parse().unwrap()).collect(),
+            None => vec![WORD_SQUARE_WIDTH, WORD_SQUARE_HEIGHT],
+        },
+        unordered: args.is_present("unordered"),
+    };
+    let min_word_len:usize = args.value_of("min-word-len").unwrap().parse().unwrap();
+    if opts.separator.chars().any(|c| encode(c).is_none()) {
+        eprintln!("Warning: separator {:?} can't be encoded, so compute will skip every compound.", opts.separator);
+    } else if opts.separator.contains(' ') {
+        eprintln!("Note: compounds contain spaces, so compute and build-index need --phrases to use them.");
+    }
+
+    let mut words = Vec::new();
+    for line_result in open_input(args.value_of("input-filename").unwrap())?.lines() {
+        let word = line_result?;
+        if word.chars().count() >= min_word_len {
+            words.push(word);
+        }
+    }
+    let combiner = combine::Combiner::new(words);
+
+    let mut out = WordlistWriter::single(args.value_of("output-filename").unwrap())?;
+    combiner.write_all(&opts, &mut out)?;
+    out.finish()
+}
+
+fn decode_results_command(args:&ArgMatches) -> io::Result<()> {
+    let format = OutputFormat::from_name(args.value_of("format").unwrap()).unwrap();
+    let path = args.value_of("input-filename").unwrap();
+    let stdout = io::stdout();
+    let mut out = BufWriter::new(stdout.lock());
+    if path == "-" {
+        let stdin = io::stdin();
+        results::decode_results(stdin.lock(), &mut out, format)?;
+    } else {
+        results::decode_results(BufReader::new(File::open(path)?), &mut out, format)?;
+    }
+    Ok(())
+}
+
+/// How lines of a wordlist are turned into words for the index.
+struct WordlistOptions {
     ignore_unencodeable: bool,
-) -> io::Result<(u32, u32, WordIndex)> {
-    let mut index = WordIndex::default();
+    text: TextOptions,
+    phrases: PhraseOptions,
+    schema: Option<Schema>,
+}
 
-    let mut count_row_words = 0;
-    #[cfg(not(feature = "square"))]
-    let mut count_col_words = 0;
+impl WordlistOptions {
+    fn from_args(args:&ArgMatches, ignore_unencodeable:bool) -> io::Result<WordlistOptions> {
+        Ok(WordlistOptions{
+            ignore_unencodeable,
+            text: TextOptions::from_args(args),
+            phrases: PhraseOptions::from_args(args),
+            schema: Schema::from_args(args)?,
+        })
+    }
+}
+
+/// A word that passed every check, ready to be indexed for any size it fits.
+struct EncodedWord {
+    codes: Vec<u8>,
+    meta: Vec<String>,
+}
 
+/// Reads the words of `f_in` with one of the given lengths, in chars.
+fn read_words(
+    f_in: impl BufRead,
+    opts: &WordlistOptions,
+    filter: &mut WordFilter,
+    inflections: &mut InflectionFilter,
+    lengths: &[usize],
+) -> io::Result<Vec<EncodedWord>> {
+    let mut words = Vec::new();
     let lines = f_in.lines();
     for line_result in lines {
-        let word = line_result?;
+        let line = line_result?;
+        let (word, mut meta) = match opts.schema {
+            Some(ref schema) => schema.split(&line),
+            None => (line, Vec::new()),
+        };
+        let word = opts.text.apply(&word);
 
         let chars:Vec<char> = word.chars().collect();
-        if chars.len() != WORD_SQUARE_WIDTH && chars.len() != WORD_SQUARE_HEIGHT { continue }
+        if !lengths.contains(&chars.len()) { continue }
+        let word = match opts.phrases.check(&word) {
+            Ok(word) => word,
+            Err(reason) => {
+                if !opts.ignore_unencodeable {
+                    eprintln!("Skipping {:?}, a phrase {}", word, reason);
+                }
+                continue
+            },
+        };
+        if inflections.check(&word, &mut meta).is_some() { continue }
+        if filter.check(&word, &meta).is_some() { continue }
         let mut codes = Vec::new();
         let mut all_encoded = true;
-        for c in chars.clone() {
+        for c in word.chars() {
             match encode(c) {
                 Some(code) => codes.push(code),
                 None => {
@@ -416,20 +1096,120 @@ fn make_words_index(
             }
         }
         if !all_encoded {
-            if !ignore_unencodeable {
+            if !opts.ignore_unencodeable {
                 eprintln!("Skipping {:?}, not all could be encoded",chars);
             }
             continue
         }
-        if codes.len() == WORD_SQUARE_WIDTH {
-            count_row_words += 1;
+        words.push(EncodedWord{codes, meta});
+    }
+    Ok(words)
+}
+
+/// The words given to compute or build-index, read once and indexed for as
+/// many sizes as needed.
+struct LoadedWords {
+    meta_columns: Vec<String>,
+    rows: Vec<EncodedWord>,
+    /// Words from --col-words, or None if the rows list is used both ways.
+    cols: Option<Vec<EncodedWord>>,
+    /// From --max-memory, the most bytes an index of these words may take.
+    max_memory: Option<u64>,
+}
+
+impl LoadedWords {
+    fn index<const W:usize, const H:usize>(&self) -> (u32, u32, WordIndex<W, H>) {
+        let mut index = WordIndex{meta: WordMetadata::new(self.meta_columns.clone()), ..Default::default()};
+        match self.cols {
+            None => {
+                let (count_row_words, count_col_words) = index_words(&mut index, &self.rows, Orientation::Both, self.max_memory);
+                (count_row_words, count_col_words, index)
+            },
+            Some(ref cols) => {
+                // Made up front so a column list with no words that fit leaves
+                // the index empty, rather than square grids using the rows for
+                // columns too.
+                index.cols_mut();
+                let (count_row_words, _) = index_words(&mut index, &self.rows, Orientation::Rows, self.max_memory);
+                let (_, count_col_words) = index_words(&mut index, cols, Orientation::Cols, self.max_memory);
+                (count_row_words, count_col_words, index)
+            },
+        }
+    }
+}
+
+/// Reads the wordlist arguments shared by compute and build-index, keeping
+/// words that fit a row of one of `widths` or a column of one of `heights`.
+fn load_words(
+    args: &ArgMatches,
+    ignore_unencodeable: bool,
+    widths: &[usize],
+    heights: &[usize],
+) -> io::Result<LoadedWords> {
+    let row_path = args.value_of("row-words").or(args.value_of("wordlist"));
+    let col_path = args.value_of("col-words").or(args.value_of("wordlist"));
+    let (row_path, col_path) = match (row_path, col_path) {
+        (Some(r), Some(c)) => (r, c),
+        _ => clap::Error::with_description(
+            "A wordlist is required unless both --row-words and --col-words are given",
+            clap::ErrorKind::MissingRequiredArgument,
+        ).exit(),
+    };
+    let opts = WordlistOptions::from_args(args, ignore_unencodeable)?;
+    let mut filter = WordFilter::from_args(args, &opts.text, opts.schema.as_ref())?;
+    let mut inflections = InflectionFilter::from_args(args, &opts.text, opts.schema.as_ref())?;
+    let meta_columns = opts.schema.as_ref().map(|s| s.meta_columns()).unwrap_or_default();
+    let max_memory = args.value_of("max-memory").map(|m| memory::parse_bytes(m).unwrap());
+    let res = if row_path == col_path {
+        let lengths:Vec<usize> = widths.iter().chain(heights).cloned().collect();
+        let rows = read_words(open_input(row_path)?, &opts, &mut filter, &mut inflections, &lengths)?;
+        LoadedWords{meta_columns, rows, cols: None, max_memory}
+    } else {
+        let rows = read_words(open_input(row_path)?, &opts, &mut filter, &mut inflections, widths)?;
+        let cols = read_words(open_input(col_path)?, &opts, &mut filter, &mut inflections, heights)?;
+        LoadedWords{meta_columns, rows, cols: Some(cols), max_memory}
+    };
+    if !args.is_present("quiet") {
+        filter.report();
+        inflections.report();
+    }
+    Ok(res)
+}
+
+fn index_words<const W:usize, const H:usize>(
+    index: &mut WordIndex<W, H>,
+    words: &[EncodedWord],
+    orientation: Orientation,
+    max_memory: Option<u64>,
+) -> (u32, u32) {
+    let mut count_row_words = 0;
+    let mut count_col_words = 0;
+
+    // Square grids index words once for both directions unless told otherwise.
+    let fill_rows = orientation != Orientation::Cols;
+    let fill_cols = orientation == Orientation::Cols
+        || (orientation == Orientation::Both && W != H);
+
+    for (n, EncodedWord{codes, meta}) in words.iter().enumerate() {
+        let fits_row = fill_rows && codes.len() == W;
+        let fits_col = fill_cols && codes.len() == H;
+        if !fits_row && !fits_col { continue }
+        if let Some(max) = max_memory {
+            check_index_growth(index, fits_row, fits_col, max, n, words.len());
+        }
+        if !index.meta.is_empty() {
+            index.meta.insert(codes, meta.clone());
+        }
+        if fits_row {
             let words_index = index.rows_mut();
-            let mut word = WideWord::default();
+            let mut word = [0u8; W];
             for (i, code) in codes.iter().enumerate() {
                 word[i] = *code;
             }
-            for j in 0..WORD_SQUARE_WIDTH {
-                let i = (WORD_SQUARE_WIDTH - 1) - j;
+            // The last letter is already in its prefix's set only if the word was seen before.
+            let mut duplicate = false;
+            for j in 0..W {
+                let i = (W - 1) - j;
                 // for i in WORD_SQUARE_ORDER..0 including 0, excluding WORD_SQUARE_ORDER
                 let code = word[i];
                 word[i] = 255u8;
@@ -437,19 +1217,26 @@ fn make_words_index(
                     //println!("Inserting {:?}", word);
                     words_index.insert(word, CharSet::default());
                 }
-                words_index.get_mut(&word).unwrap().add(code);
+                let set = words_index.get_mut(&word).unwrap();
+                if j == 0 {
+                    duplicate = set.has(code);
+                }
+                set.add(code);
+            }
+            if !duplicate {
+                count_row_words += 1;
             }
         }
-        #[cfg(not(feature = "square"))]
-        if codes.len() == WORD_SQUARE_HEIGHT {
-            count_col_words += 1;
+        if fits_col {
             let words_index = index.cols_mut();
-            let mut word = TallWord::default();
+            let mut word = [0u8; H];
             for (i, code) in codes.iter().enumerate() {
                 word[i] = *code;
             }
-            for j in 0..WORD_SQUARE_HEIGHT {
-                let i = (WORD_SQUARE_HEIGHT - 1) - j;
+            // The last letter is already in its prefix's set only if the word was seen before.
+            let mut duplicate = false;
+            for j in 0..H {
+                let i = (H - 1) - j;
                 // for i in WORD_SQUARE_ORDER..0 including 0, excluding WORD_SQUARE_ORDER
                 let code = word[i];
                 word[i] = 255u8;
@@ -457,15 +1244,85 @@ fn make_words_index(
                     //println!("Inserting {:?}", word);
                     words_index.insert(word, CharSet::default());
                 }
-                words_index.get_mut(&word).unwrap().add(code);
+                let set = words_index.get_mut(&word).unwrap();
+                if j == 0 {
+                    duplicate = set.has(code);
+                }
+                set.add(code);
+            }
+            if !duplicate {
+                count_col_words += 1;
             }
         }
     }
 
-    #[cfg(feature = "square")]
-    let count_col_words = count_row_words;
+    if W == H && orientation == Orientation::Both {
+        count_col_words = count_row_words;
+    }
 
-    return Ok((count_row_words, count_col_words, index));
+    (count_row_words, count_col_words)
+}
+
+/// Exits with an error if adding a word could make a map of `index` grow
+/// past `max_memory` bytes. A growing map holds its old table while copying it to the new one,
+/// so the check is made before the new one is allocated. `done` of `total`
+/// words have been indexed so far, for the error message.
+fn check_index_growth<const W:usize, const H:usize>(
+    index: &WordIndex<W, H>,
+    fits_row: bool,
+    fits_col: bool,
+    max_memory: u64,
+    done: usize,
+    total: usize,
+) {
+    let rows = index.rows();
+    let row_growth = if fits_row && rows.len() + W > rows.capacity() {
+        map_bytes(rows.capacity() + W, mem::size_of::<([u8; W], CharSet)>())
+    } else {
+        0
+    };
+    let col_growth = match index.separate_cols() {
+        Some(cols) if fits_col && cols.len() + H > cols.capacity() => {
+            map_bytes(cols.capacity() + H, mem::size_of::<([u8; H], CharSet)>())
+        },
+        _ => 0,
+    };
+    let peak = index.row_bytes() + index.col_bytes() + row_growth.max(col_growth);
+    if peak <= max_memory {
+        return;
+    }
+    clap::Error::with_description(
+        &format!(
+            "growing the {}x{} index would take ~{}, over --max-memory {}. Stopped after {} of {} words, with {}. \
+             Raise --max-memory, or use fewer words, eg with --min-freq, --blocklist or --exclude-regex.",
+            W, H, format_bytes(peak), format_bytes(max_memory), done, total, index.usage(),
+        ),
+        clap::ErrorKind::Io,
+    ).exit()
+}
+
+fn build_index_command(args:&ArgMatches) -> io::Result<()> {
+    let loud = !args.is_present("quiet");
+    let ignore_unencodeable = args.is_present("ignore-unencodeable");
+
+    if loud {
+        eprintln!("Word square order is {}x{}", WORD_SQUARE_WIDTH, WORD_SQUARE_HEIGHT);
+        eprintln!("Start: creating index.");
+    }
+
+    let words = load_words(args, ignore_unencodeable, &[WORD_SQUARE_WIDTH], &[WORD_SQUARE_HEIGHT])?;
+    let (count_row_words, count_col_words, index) = words.index::<WORD_SQUARE_WIDTH, WORD_SQUARE_HEIGHT>();
+    if loud {
+        eprintln!("Finished creating index, {} words x {} words.", count_row_words, count_col_words);
+        eprintln!("Index has {}.", index.usage());
+    }
+
+    let mut out = BufWriter::new(File::create(args.value_of("index-filename").unwrap())?);
+    index_file::write_index(&mut out, count_row_words, count_col_words, &index)?;
+    if loud {
+        eprintln!("Wrote index.");
+    }
+    Ok(())
 }
 
 fn compute_command(args:&ArgMatches) -> io::Result<()> {
@@ -473,122 +1330,499 @@ fn compute_command(args:&ArgMatches) -> io::Result<()> {
     let ignore_empty_wordlist = args.is_present("ignore-empty-wordlist");
     let ignore_unencodeable = args.is_present("ignore-unencodeable");
 
+    let num_threads = match args.value_of("threads").map(|t| t.parse::<u32>().unwrap()) {
+        Some(0) | None => available_threads(),
+        Some(n) => n,
+    };
+
+    if let Some(spec) = args.value_of("sizes") {
+        return compute_sizes(args, &parse_sizes(spec).unwrap(), num_threads);
+    }
+
     if loud {
         eprintln!("Word square order is {}x{}", WORD_SQUARE_WIDTH, WORD_SQUARE_HEIGHT);
         eprintln!("Start: creating index.");
     }
 
-    let num_threads:u32 = args.value_of("threads").unwrap().parse().unwrap();
-
-    let plain_f = File::open(args.value_of("wordlist").unwrap())?;
-    let f = BufReader::new(plain_f);
-    
-    let (count_row_words, count_col_words, index) = make_words_index(f, ignore_unencodeable)?;
-    if !ignore_empty_wordlist && (index.rows().is_empty() || index.cols().is_empty()) {
+    let start = std::time::Instant::now();
+    let (count_row_words, count_col_words, index) = match args.value_of("index") {
+        Some(path) => {
+            let mut f = BufReader::new(File::open(path)?);
+            index_file::read_index(&mut f)?
+        },
+        None => load_words(args, ignore_unencodeable, &[WORD_SQUARE_WIDTH], &[WORD_SQUARE_HEIGHT])?.index(),
+    };
+    if !ignore_empty_wordlist && index.is_empty() {
         panic!("No words in wordlist!");
     }
     if loud {
         eprintln!("Finished creating index, {} words x {} words.", count_row_words, count_col_words);
+        eprintln!("Index has {}.", index.usage());
+        report_threads(args, num_threads);
     }
 
-    let (m2w_tx, m2w_rx) = spmc::channel::<(WordSquare,u8)>();
-    let (w2m_tx, w2m_rx) = std::sync::mpsc::sync_channel(16);
-    let mut worker_handles = Vec::new();
+    let (squares, nodes) = compute_size::<WORD_SQUARE_WIDTH, WORD_SQUARE_HEIGHT, WORD_SQUARE_SIZE>(args, index, num_threads, None)?;
+    if let Some(path) = args.value_of("summary") {
+        let summary = SizeSummary{
+            width: WORD_SQUARE_WIDTH,
+            height: WORD_SQUARE_HEIGHT,
+            row_words: count_row_words,
+            col_words: count_col_words,
+            squares,
+            nodes,
+            elapsed: start.elapsed(),
+        };
+        let mut out = BufWriter::new(File::create(path)?);
+        summary::write_summary(&mut out, SummaryFormat::from_args(args), &[summary])?;
+    }
+    Ok(())
+}
 
-    if loud {
+fn report_threads(args:&ArgMatches, num_threads:u32) {
+    if args.value_of("threads").is_some_and(|t| t != "0") {
         eprintln!("Creating {} worker threads.", num_threads);
+    } else {
+        eprintln!("Creating {} worker threads, one per available CPU.", num_threads);
     }
+}
+
+/// Searches every `W` by `H` square in `index`, writing them to `output` or
+/// to STDOUT. Returns how many were found and the number of nodes explored.
+fn compute_size<const W:usize, const H:usize, const S:usize>(
+    args:&ArgMatches,
+    index:WordIndex<W, H>,
+    num_threads:u32,
+    output:Option<&Path>,
+) -> io::Result<(u64, u64)> {
+    let loud = !args.is_present("quiet");
+    let format = OutputFormat::from_name(args.value_of("format").unwrap()).unwrap();
+    let split_depth = match args.value_of("split-depth").unwrap() {
+        "auto" => None,
+        depth => Some(depth.parse::<usize>().unwrap()),
+    };
+    if let Some(depth) = split_depth.filter(|&d| d >= S) {
+        return Err(invalid_data(&format!(
+            "--split-depth {} must be less than {}, the number of cells in {}x{}", depth, S, W, H,
+        )));
+    }
+
+    let out:Box<dyn Write + Send> = match output {
+        Some(path) => Box::new(File::create(path)?),
+        None => Box::new(io::stdout()),
+    };
+
+    let (w2m_tx, w2m_rx) = std::sync::mpsc::sync_channel::<Vec<[u8; S]>>(16);
 
     let index_arc = std::sync::Arc::new(index);
+
+    let printer_index = std::sync::Arc::clone(&index_arc);
+    let printing_thread = thread::spawn(move || -> io::Result<u64> {
+        let mut writer = ResultWriter::new(BufWriter::new(out), format, W, H)?.with_meta(&printer_index.meta);
+        let mut count = 0u64;
+        while let Ok(batch) = w2m_rx.recv() {
+            for sq in &batch {
+                writer.write(sq)?;
+            }
+            count += batch.len() as u64;
+            // Squares still show up as soon as their batch is done.
+            writer.flush()?;
+        }
+        writer.finish()?;
+        Ok(count)
+    });
     
-    for _ in 0..num_threads {
-        let rxc = m2w_rx.clone();
-        let txc = w2m_tx.clone();
-        let my_index = std::sync::Arc::clone(&index_arc);
-        worker_handles.push(
-            thread::spawn( move || {
-                while let Ok(msg) = rxc.recv() {
-                    compute(
-                        &my_index,
-                        msg.0,
-                        msg.1,
-                        WORD_SQUARE_SIZE as u8,
-                        |a,b| txc.send((a,b)).unwrap()
-                    );
+    if loud {
+        eprintln!("Starting.");
+    }
+
+    let start = std::time::Instant::now();
+    let send = move |batch| w2m_tx.send(batch).unwrap();
+    let (worker_stats, nodes, depth_stats) = if args.is_present("search-stats") {
+        let (worker_stats, nodes, stats) = search::<W, H, S, DepthStats<S>, _>(index_arc, split_depth, num_threads, loud, send);
+        (worker_stats, nodes, Some(stats))
+    } else {
+        let (worker_stats, nodes, NoStats) = search::<W, H, S, NoStats, _>(index_arc, split_depth, num_threads, loud, send);
+        (worker_stats, nodes, None)
+    };
+    let count = printing_thread.join().unwrap()?;
+    if loud {
+        scheduler::report(&worker_stats, start.elapsed());
+    }
+    if let Some(stats) = depth_stats {
+        eprintln!("Search statistics for {}x{}:", W, H);
+        stats.write_table(&mut io::stderr(), W)?;
+    }
+
+    Ok((count, nodes))
+}
+
+/// Splits the search of `index` and runs it on `num_threads` threads,
+/// counting its steps with `St`. Returns the workers' stats and the total
+/// number of nodes explored.
+fn search<const W:usize, const H:usize, const S:usize, St, F>(
+    index:std::sync::Arc<WordIndex<W, H>>,
+    split_depth:Option<usize>,
+    num_threads:u32,
+    loud:bool,
+    on_batch:F,
+) -> (Vec<scheduler::WorkerStats>, u64, St)
+    where St:SearchStats + 'static, F:Fn(Vec<[u8; S]>) + Send + Sync + 'static
+{
+    let mut stats = St::default();
+    let root = ([255u8; S], 0);
+    let (roots, split_nodes) = match split_depth {
+        None => scheduler::split_adaptive(&index, root, num_threads as usize * SPLIT_TASKS_PER_THREAD, &mut stats),
+        Some(depth) => scheduler::split_at_depth(&index, root, depth as u8, &mut stats),
+    };
+    if loud {
+        let depth = roots.first().map(|t| t.1).unwrap_or(0);
+        let branching = if depth == 0 { 0.0 } else { (roots.len() as f64).powf(1.0 / depth as f64) };
+        eprintln!("Split into {} tasks at depth {} (about {:.1} choices per cell).", roots.len(), depth, branching);
+    }
+    let (worker_stats, worker_search) = scheduler::run::<W, H, S, St, F>(index, roots, num_threads as usize, on_batch);
+    stats.merge(&worker_search);
+    let nodes = split_nodes + worker_stats.iter().map(|s| s.nodes).sum::<u64>();
+    (worker_stats, nodes, stats)
+}
+
+/// Parses a `--sizes` list such as "5x5,8x5" or "2x2..15x15". Sizes taller
+/// than they are wide aren't allowed, as their squares are the transposes of
+/// the wider size's, and a range skips them.
+fn parse_sizes(spec:&str) -> Result<Vec<(usize, usize)>, String> {
+    fn parse_size(size:&str) -> Result<(usize, usize), String> {
+        let mut dims = size.trim().splitn(2, 'x').map(|d| d.trim().parse::<usize>());
+        match (dims.next(), dims.next()) {
+            (Some(Ok(w)), Some(Ok(h))) if !(MIN_SIZE..=MAX_SIZE).contains(&w) || !(MIN_SIZE..=MAX_SIZE).contains(&h) => {
+                Err(format!("{:?} is out of range, both sides must be from {} to {}", size, MIN_SIZE, MAX_SIZE))
+            },
+            (Some(Ok(w)), Some(Ok(h))) if h > w => {
+                Err(format!("{:?} is taller than it is wide, use {}x{} and transpose its squares", size, h, w))
+            },
+            (Some(Ok(w)), Some(Ok(h))) => Ok((w, h)),
+            _ => Err(format!("{:?} is not a size like \"8x5\"", size)),
+        }
+    }
+
+    let mut sizes = Vec::new();
+    for item in spec.split(',') {
+        match item.find("..") {
+            Some(at) => {
+                let (w1, h1) = parse_size(&item[..at])?;
+                let (w2, h2) = parse_size(&item[at + 2..])?;
+                for w in w1..=w2 {
+                    for h in h1..=h2.min(w) {
+                        sizes.push((w, h));
+                    }
                 }
-            })
-        );
+            },
+            None => sizes.push(parse_size(item)?),
+        }
     }
+    if sizes.is_empty() {
+        return Err(String::from("No sizes given"));
+    }
+    let mut seen = FnvHashSet::default();
+    sizes.retain(|&size| seen.insert(size));
+    if !cfg!(feature = "sweep") {
+        if let Some(&(w, h)) = sizes.iter().find(|&&size| size != (WORD_SQUARE_WIDTH, WORD_SQUARE_HEIGHT)) {
+            return Err(format!(
+                "{}x{} needs a binary built with the sweep feature, this one only has {}x{}",
+                w, h, WORD_SQUARE_WIDTH, WORD_SQUARE_HEIGHT,
+            ));
+        }
+    }
+    Ok(sizes)
+}
 
-    drop(w2m_tx);
+/// Calls `$f::<W, H, {W * H}>$args` for a size only known at run time, which
+/// `parse_sizes` has checked is in range and no taller than it is wide. Only
+/// those sizes are compiled in, to keep build times down.
+#[cfg(feature = "sweep")]
+macro_rules! with_size {
+    ($w:expr, $h:expr, $f:ident $args:tt) => {
+        match $w {
+            2 => with_size!(@heights 2, $h, $f, $args, [2]),
+            3 => with_size!(@heights 3, $h, $f, $args, [2 3]),
+            4 => with_size!(@heights 4, $h, $f, $args, [2 3 4]),
+            5 => with_size!(@heights 5, $h, $f, $args, [2 3 4 5]),
+            6 => with_size!(@heights 6, $h, $f, $args, [2 3 4 5 6]),
+            7 => with_size!(@heights 7, $h, $f, $args, [2 3 4 5 6 7]),
+            8 => with_size!(@heights 8, $h, $f, $args, [2 3 4 5 6 7 8]),
+            9 => with_size!(@heights 9, $h, $f, $args, [2 3 4 5 6 7 8 9]),
+            10 => with_size!(@heights 10, $h, $f, $args, [2 3 4 5 6 7 8 9 10]),
+            11 => with_size!(@heights 11, $h, $f, $args, [2 3 4 5 6 7 8 9 10 11]),
+            12 => with_size!(@heights 12, $h, $f, $args, [2 3 4 5 6 7 8 9 10 11 12]),
+            13 => with_size!(@heights 13, $h, $f, $args, [2 3 4 5 6 7 8 9 10 11 12 13]),
+            14 => with_size!(@heights 14, $h, $f, $args, [2 3 4 5 6 7 8 9 10 11 12 13 14]),
+            15 => with_size!(@heights 15, $h, $f, $args, [2 3 4 5 6 7 8 9 10 11 12 13 14 15]),
+            _ => unreachable!(),
+        }
+    };
+    (@heights $width:literal, $h:expr, $f:ident, $args:tt, [$($height:literal)*]) => {
+        match $h {
+            $( $height => $f::<$width, $height, {$width * $height}> $args, )*
+            _ => unreachable!(),
+        }
+    };
+}
+
+/// Without the sweep feature `parse_sizes` only allows this binary's own
+/// size, so that's the only one compiled.
+#[cfg(not(feature = "sweep"))]
+macro_rules! with_size {
+    ($w:expr, $h:expr, $f:ident $args:tt) => {{
+        debug_assert_eq!(($w, $h), (WORD_SQUARE_WIDTH, WORD_SQUARE_HEIGHT));
+        $f::<WORD_SQUARE_WIDTH, WORD_SQUARE_HEIGHT, WORD_SQUARE_SIZE> $args
+    }};
+}
 
-    let printing_thread = thread::spawn(move || {
-        while let Ok(msg) = w2m_rx.recv() {
-            print_word_square(msg.0);
+/// Indexes `words` for one size of a sweep and searches it, writing the
+/// squares to a file named after the size in `out_dir`.
+fn sweep_size<const W:usize, const H:usize, const S:usize>(
+    args:&ArgMatches,
+    words:&LoadedWords,
+    num_threads:u32,
+    out_dir:&Path,
+) -> io::Result<SizeSummary> {
+    let loud = !args.is_present("quiet");
+    let start = std::time::Instant::now();
+    let (row_words, col_words, index) = words.index::<W, H>();
+    let mut summary = SizeSummary{width: W, height: H, row_words, col_words, ..Default::default()};
+    if index.is_empty() {
+        if loud {
+            eprintln!("{}x{}: no words of the necessary lengths, skipping.", W, H);
         }
-    });
-    
-    let code_array = [255u8; WORD_SQUARE_SIZE];
+        return Ok(summary);
+    }
+    if loud {
+        eprintln!("{}x{}: {} words x {} words, index has {}.", W, H, row_words, col_words, index.usage());
+    }
+    let format = OutputFormat::from_name(args.value_of("format").unwrap()).unwrap();
+    let path = out_dir.join(format!("{}x{}.{}", W, H, format.extension()));
+    let (squares, nodes) = compute_size::<W, H, S>(args, index, num_threads, Some(&path))?;
+    summary.squares = squares;
+    summary.nodes = nodes;
+    summary.elapsed = start.elapsed();
+    Ok(summary)
+}
+
+/// Runs compute for each of `sizes`, reading the wordlist only once.
+fn compute_sizes(args:&ArgMatches, sizes:&[(usize, usize)], num_threads:u32) -> io::Result<()> {
+    let loud = !args.is_present("quiet");
+    let ignore_unencodeable = args.is_present("ignore-unencodeable");
+    let out_dir = Path::new(args.value_of("output-dir").unwrap());
+    let jobs = args.value_of("size-jobs").unwrap().parse::<usize>().unwrap().min(sizes.len());
+    std::fs::create_dir_all(out_dir)?;
 
     if loud {
-        eprintln!("Starting.");
+        eprintln!("Start: reading words for {} sizes.", sizes.len());
+    }
+    let widths:Vec<usize> = sizes.iter().map(|s| s.0).collect();
+    let heights:Vec<usize> = sizes.iter().map(|s| s.1).collect();
+    let words = load_words(args, ignore_unencodeable, &widths, &heights)?;
+    if loud {
+        eprintln!("Finished reading words.");
+        report_threads(args, num_threads);
+    }
+
+    // Sizes are handed out in order to `jobs` threads, each running one at a time.
+    let next = std::sync::atomic::AtomicUsize::new(0);
+    let summaries = std::sync::Mutex::new(Vec::new());
+    thread::scope(|scope| -> io::Result<()> {
+        let handles:Vec<_> = (0..jobs).map(|_| scope.spawn(|| -> io::Result<()> {
+            loop {
+                let i = next.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+                let Some(&(w, h)) = sizes.get(i) else { return Ok(()) };
+                let summary = with_size!(w, h, sweep_size(args, &words, num_threads, out_dir))?;
+                summaries.lock().unwrap().push((i, summary));
+            }
+        })).collect();
+        for handle in handles {
+            handle.join().unwrap()?;
+        }
+        Ok(())
+    })?;
+
+    let mut summaries = summaries.into_inner().unwrap();
+    summaries.sort_by_key(|&(i, _)| i);
+    let summaries:Vec<SizeSummary> = summaries.into_iter().map(|(_, s)| s).collect();
+    let format = SummaryFormat::from_args(args);
+    let path = match args.value_of("summary") {
+        Some(path) => PathBuf::from(path),
+        None => out_dir.join(format!("summary.{}", format.extension())),
+    };
+    summary::write_summary(&mut BufWriter::new(File::create(&path)?), format, &summaries)?;
+    if loud {
+        eprintln!("Finished {} sizes, wrote a summary to {}:", summaries.len(), path.display());
+        summary::write_summary(&mut io::stderr(), SummaryFormat::Markdown, &summaries)?;
     }
-    
-    compute(
-        index_arc.as_ref(),
-        code_array,
-        0u8,
-        WORD_SQUARE_WIDTH as u8,
-        |ca, idx| m2w_tx.send((ca,idx)).unwrap()
-    );
-
-    drop(m2w_tx);
-    //println!("Dropped");
-    for h in worker_handles {
-        h.join().unwrap();
-        //println!("Worker finished");
-    }
-    printing_thread.join().unwrap();
-    //println!("printing thread finished");
-    
-    /*let mut char_counts:Vec<(char,u64)> = unused_chars.drain().collect();
-    char_counts.sort_unstable_by_key(|t| t.1);
-    for (k,v) in char_counts.iter() {
-        println!("Char {:?} had {} instances", k, v);
-    }*/
     Ok(())
 }
 
+fn bench_command(args:&ArgMatches) -> io::Result<()> {
+    let loud = !args.is_present("quiet");
+    let sizes = match args.value_of("sizes") {
+        Some(spec) => parse_sizes(spec).unwrap(),
+        None if cfg!(feature = "sweep") => parse_sizes(bench::REFERENCE_SIZES).unwrap(),
+        None => vec![(WORD_SQUARE_WIDTH, WORD_SQUARE_HEIGHT)],
+    };
+    let num_threads = match args.value_of("threads").map_or(1, |t| t.parse::<u32>().unwrap()) {
+        0 => available_threads(),
+        n => n,
+    } as usize;
+    let runs = args.value_of("runs").unwrap().parse::<usize>().unwrap();
+    let format = SummaryFormat::from_name(args.value_of("format").unwrap()).unwrap();
+
+    let widths:Vec<usize> = sizes.iter().map(|s| s.0).collect();
+    let heights:Vec<usize> = sizes.iter().map(|s| s.1).collect();
+    let words = if args.is_present("wordlist") || args.is_present("row-words") {
+        load_words(args, args.is_present("ignore-unencodeable"), &widths, &heights)?
+    } else {
+        let lengths:Vec<usize> = widths.iter().chain(&heights).cloned().collect();
+        let count = args.value_of("generated-words").unwrap().parse().unwrap();
+        bench::generate_words(&lengths, count, args.value_of("seed").unwrap().parse().unwrap())
+    };
+
+    let mut results = Vec::new();
+    for &(w, h) in &sizes {
+        if loud {
+            eprintln!("Running {}x{}.", w, h);
+        }
+        results.push(with_size!(w, h, bench_size(&words, num_threads, runs)).row());
+    }
+    summary::write_table(&mut io::stdout(), format, bench::COLUMNS, &results)
+}
+
+fn estimate_command(args:&ArgMatches) -> io::Result<()> {
+    let loud = !args.is_present("quiet");
+    let num_threads = match args.value_of("threads").map(|t| t.parse::<u32>().unwrap()) {
+        Some(0) | None => available_threads(),
+        Some(n) => n,
+    };
+    let mut opts = EstimateOptions::from_args(args, num_threads);
+    let format = SummaryFormat::from_name(args.value_of("format").unwrap()).unwrap();
+
+    let mut results = Vec::new();
+    if let Some(path) = args.value_of("index") {
+        let mut f = BufReader::new(File::open(path)?);
+        let (row_words, col_words, index) = index_file::read_index(&mut f)?;
+        if loud {
+            eprintln!("Estimating {}x{}.", WORD_SQUARE_WIDTH, WORD_SQUARE_HEIGHT);
+        }
+        let estimate = estimate::estimate_index::<WORD_SQUARE_WIDTH, WORD_SQUARE_HEIGHT, WORD_SQUARE_SIZE>(&index, row_words, col_words, &mut opts);
+        results.push(estimate.row());
+    } else if let Some(spec) = args.value_of("sizes") {
+        let sizes = parse_sizes(spec).unwrap();
+        let widths:Vec<usize> = sizes.iter().map(|s| s.0).collect();
+        let heights:Vec<usize> = sizes.iter().map(|s| s.1).collect();
+        let words = load_words(args, args.is_present("ignore-unencodeable"), &widths, &heights)?;
+        for &(w, h) in &sizes {
+            if loud {
+                eprintln!("Estimating {}x{}.", w, h);
+            }
+            results.push(with_size!(w, h, estimate_size(&words, &mut opts)).row());
+        }
+    } else {
+        // Called directly rather than through with_size!, which only has
+        // sizes that are at least as wide as they are tall.
+        let words = load_words(args, args.is_present("ignore-unencodeable"), &[WORD_SQUARE_WIDTH], &[WORD_SQUARE_HEIGHT])?;
+        if loud {
+            eprintln!("Estimating {}x{}.", WORD_SQUARE_WIDTH, WORD_SQUARE_HEIGHT);
+        }
+        let estimate = estimate_size::<WORD_SQUARE_WIDTH, WORD_SQUARE_HEIGHT, WORD_SQUARE_SIZE>(&words, &mut opts);
+        results.push(estimate.row());
+    }
+    summary::write_table(&mut io::stdout(), format, estimate::COLUMNS, &results)
+}
+
+/// Number of CPUs this process may use, or 1 if that can't be found out.
+/// On Linux this accounts for sched affinity masks and cgroup CPU quotas.
+fn available_threads() -> u32 {
+    thread::available_parallelism().map(|n| n.get() as u32).unwrap_or(1)
+}
+
 const DEBUG_MODE:bool = false;
 
+/// How many tasks per thread `--split-depth auto` aims for before threads start.
+const SPLIT_TASKS_PER_THREAD:usize = 32;
+
 
-fn compute<T:FnMut(WordSquare,u8)>(
-    words_index_arg:&WordIndex,
-    mut code_array:WordSquare,
+/// Gives away the choices not yet tried at the shallowest cell before `at_idx`
+/// that has any, as tasks starting just after that cell, and removes them from
+/// this search. Returns how many tasks were given away.
+fn donate_siblings<const S:usize>(
+    code_array:&[u8; S],
+    charset_array:&mut [CharSet; S],
+    start_idx:u8,
+    at_idx:u8,
+    donor:&mut impl Donate<S>,
+    stats:&mut impl SearchStats,
+) -> u64 {
+    for idx in (start_idx as usize)..(at_idx as usize) {
+        // Codes up to and including the current one have been tried.
+        let tried = CharSetBits::MAX >> (CharSetBits::BITS - 1 - code_array[idx] as u32);
+        let untried = charset_array[idx].internal & !tried;
+        if untried == 0 {
+            continue;
+        }
+        let mut donated = 0;
+        for code in 0..ALPHABET_SIZE {
+            if untried & (1 << code) != 0 {
+                let mut task = *code_array;
+                task[idx] = code;
+                for cell in task.iter_mut().skip(idx + 1) {
+                    *cell = 255;
+                }
+                donor.donate((task, idx as u8 + 1));
+                stats.node(idx as u8);
+                donated += 1;
+            }
+        }
+        charset_array[idx] = CharSet::new(charset_array[idx].internal & tried);
+        return donated;
+    }
+    0
+}
+
+/// Searches every completion of `code_array` from `start_idx` up to (not
+/// including) `target_idx`, passing each to `on_result`. Returns the number
+/// of nodes explored, that is cells filled with a letter the index allows,
+/// counting those handed to `donor` but not the ones it searches. Each step
+/// is also passed to `stats`.
+fn compute<const W:usize, const H:usize, const S:usize, T:FnMut([u8; S],u8)>(
+    words_index_arg:&WordIndex<W, H>,
+    mut code_array:[u8; S],
     start_idx:u8,
     target_idx:u8,
     mut on_result:T,
-) {
+    donor:&mut impl Donate<S>,
+    stats:&mut impl SearchStats,
+) -> u64 {
     let mut at_idx = start_idx;
-    let mut charset_array = [CharSet::new(std::u32::MAX); WORD_SQUARE_SIZE];
+    let mut nodes = 0u64;
+    let mut steps_until_check = DONATE_CHECK_INTERVAL;
+    let mut charset_array = [CharSet::new(CharSetBits::MAX); S];
 
 
-    let row_idx = at_idx / (WORD_SQUARE_WIDTH as u8);
-    let col_idx = at_idx % (WORD_SQUARE_WIDTH as u8);
-    let row_start = row_idx*(WORD_SQUARE_WIDTH as u8);
-    let mut row_word = [255u8; WORD_SQUARE_WIDTH];
+    let row_idx = at_idx / (W as u8);
+    let col_idx = at_idx % (W as u8);
+    let row_start = row_idx*(W as u8);
+    let mut row_word = [255u8; W];
     for i in 0..col_idx {
         row_word[i as usize] = code_array[ (row_start+i) as usize ];
     }
-    let row_wordset = words_index_arg.rows()[&row_word];
+    let row_wordset = words_index_arg.row_set(&row_word);
 
-    let mut col_word = [255u8; WORD_SQUARE_HEIGHT];
+    let mut col_word = [255u8; H];
     for i in 0..row_idx {
-        col_word[i as usize] = code_array[ (col_idx + i*(WORD_SQUARE_WIDTH as u8)) as usize ];
+        col_word[i as usize] = code_array[ (col_idx + i*(W as u8)) as usize ];
     }
-    let col_wordset = words_index_arg.cols()[&col_word];
+    let col_wordset = words_index_arg.col_set(&col_word);
     
     charset_array[at_idx as usize] = col_wordset.and(&row_wordset);
+    if charset_array[at_idx as usize].internal == 0 {
+        stats.dead_end(at_idx);
+    }
 
     // wrap to go from 0 to 255
     let end_idx = start_idx.wrapping_sub(1);
@@ -607,11 +1841,11 @@ fn compute<T:FnMut(WordSquare,u8)>(
 
 
         if DEBUG_MODE {
-            let row_idx = at_idx / (WORD_SQUARE_WIDTH as u8);
-            let col_idx = at_idx % (WORD_SQUARE_WIDTH as u8);
-            for row in 0..WORD_SQUARE_HEIGHT {
-                for col in 0..WORD_SQUARE_WIDTH {
-                    print!("{}, ", decode(code_array[row*WORD_SQUARE_WIDTH + col]).unwrap());
+            let row_idx = at_idx / (W as u8);
+            let col_idx = at_idx % (W as u8);
+            for row in 0..H {
+                for col in 0..W {
+                    print!("{}, ", decode(code_array[row*W + col]).unwrap());
                 }
                 println!();
             }
@@ -619,41 +1853,59 @@ fn compute<T:FnMut(WordSquare,u8)>(
         }
 
         
+        steps_until_check -= 1;
+        if steps_until_check == 0 {
+            steps_until_check = DONATE_CHECK_INTERVAL;
+            if donor.wants_work() {
+                nodes += donate_siblings(&code_array, &mut charset_array, start_idx, at_idx, donor, stats);
+            }
+        }
+
         let cur_code = code_array[at_idx as usize];
         if DEBUG_MODE { println!("cur_code {}", cur_code); }
         let cur_charset = charset_array[at_idx as usize];
-        if cur_code == 32 {
+        if cur_code == ALPHABET_SIZE {
+            stats.backtrack(at_idx);
             code_array[at_idx as usize] = 255u8;
             at_idx = at_idx.wrapping_sub(1)
         } else if cur_charset.has(cur_code) {
+            nodes += 1;
+            stats.node(at_idx);
             at_idx += 1;
             if at_idx == target_idx {
                 //print_word_square(code_array);
+                if at_idx as usize == S {
+                    stats.solution(at_idx - 1);
+                }
                 (&mut on_result)(code_array, at_idx);
                 at_idx -= 1;
             } else {
                 code_array[at_idx as usize] = 255;
 
-                let row_idx = at_idx / (WORD_SQUARE_WIDTH as u8);
-                let col_idx = at_idx % (WORD_SQUARE_WIDTH as u8);
-                let row_start = row_idx*(WORD_SQUARE_WIDTH as u8);
-                let mut row_word = [255u8; WORD_SQUARE_WIDTH];
+                let row_idx = at_idx / (W as u8);
+                let col_idx = at_idx % (W as u8);
+                let row_start = row_idx*(W as u8);
+                let mut row_word = [255u8; W];
                 for i in 0..col_idx {
                     row_word[i as usize] = code_array[ (row_start+i) as usize ];
                 }
                 //println!("row_word {:?}", row_word);
-                let row_wordset = words_index_arg.rows()[&row_word];
+                let row_wordset = words_index_arg.row_set(&row_word);
 
-                let mut col_word = [255u8; WORD_SQUARE_HEIGHT];
+                let mut col_word = [255u8; H];
                 for i in 0..row_idx {
-                    col_word[i as usize] = code_array[ (col_idx + i*(WORD_SQUARE_WIDTH as u8)) as usize ];
+                    col_word[i as usize] = code_array[ (col_idx + i*(W as u8)) as usize ];
                 }
                 //println!("col_word {:?}", row_word);
-                let col_wordset = words_index_arg.cols()[&col_word];
+                let col_wordset = words_index_arg.col_set(&col_word);
                 
                 charset_array[at_idx as usize] = col_wordset.and(&row_wordset);
+                if charset_array[at_idx as usize].internal == 0 {
+                    stats.dead_end(at_idx);
+                }
             }
         }
     }
 
+    nodes
 }
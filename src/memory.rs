@@ -0,0 +1,61 @@
+//! Working out how much memory the index takes, for reporting it and for
+//! `--max-memory`. Every prefix of every word is its own hash map entry, so
+//! long wordlists at wide sizes can need more RAM than the machine has.
+
+use clap::Arg;
+
+pub fn memory_args<'a, 'b>() -> Vec<Arg<'a, 'b>> {
+    vec![
+        Arg::with_name("max-memory")
+            .long("max-memory")
+            .takes_value(true)
+            .validator(|arg| parse_bytes(&arg).map(|_| ()))
+            .help("Stop with an error instead of letting the index grow past this much memory, eg \"512M\" or \"8G\". Counts the index's prefix maps, not the wordlist or found squares."),
+    ]
+}
+
+/// Parses a number of bytes with an optional K, M, G or T suffix, in powers of 1024.
+pub fn parse_bytes(spec:&str) -> Result<u64, String> {
+    let spec = spec.trim();
+    let digits = spec.trim_end_matches(|c:char| c.is_ascii_alphabetic());
+    let multiplier:u64 = match spec[digits.len()..].to_ascii_uppercase().trim_end_matches("IB").trim_end_matches('B') {
+        "" => 1,
+        "K" => 1 << 10,
+        "M" => 1 << 20,
+        "G" => 1 << 30,
+        "T" => 1 << 40,
+        other => return Err(format!("Unknown unit {:?}, use K, M, G or T", other)),
+    };
+    match digits.trim().parse::<f64>() {
+        Ok(n) if n > 0.0 => Ok((n * multiplier as f64) as u64),
+        Ok(_) => Err(String::from("Must be more than 0")),
+        Err(e) => Err(format!("Must be a size like \"512M\" or \"8G\". {:?}", e)),
+    }
+}
+
+/// Formats a number of bytes in the largest unit that keeps it at least 1.
+pub fn format_bytes(bytes:u64) -> String {
+    const UNITS:&[&str] = &["bytes", "KiB", "MiB", "GiB", "TiB"];
+    let mut value = bytes as f64;
+    let mut unit = 0;
+    while value >= 1024.0 && unit + 1 < UNITS.len() {
+        value /= 1024.0;
+        unit += 1;
+    }
+    if unit == 0 {
+        format!("{} bytes", bytes)
+    } else {
+        format!("{:.1} {}", value, UNITS[unit])
+    }
+}
+
+/// Roughly how many bytes a hash map takes once it can hold `capacity`
+/// entries of `entry` bytes: buckets are a power of two kept at most 7/8
+/// full, each with a byte of control data besides the entry.
+pub fn map_bytes(capacity:usize, entry:usize) -> u64 {
+    if capacity == 0 {
+        return 0;
+    }
+    let buckets = (capacity * 8 / 7).next_power_of_two();
+    (buckets * (entry + 1)) as u64
+}
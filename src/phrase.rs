@@ -0,0 +1,72 @@
+//! Multi-word phrases such as "ice cream", where the space between words is a
+//! symbol of its own. Without `--phrases` any word containing a space is
+//! dropped, as before spaces were encodable.
+//!
+//! The space only gets a code in builds with the phrases feature, so that
+//! other builds keep 32-bit CharSets and 5-bit binary results.
+
+use clap::{Arg, ArgMatches};
+
+/// Arguments read by `PhraseOptions::from_args`.
+pub fn phrase_args<'a, 'b>() -> Vec<Arg<'a, 'b>> {
+    vec![
+        Arg::with_name("phrases")
+            .long("phrases")
+            .help("Keep multi-word phrases, treating the space between words as a letter. Phrases with leading, trailing or double spaces are still dropped."),
+        Arg::with_name("phrase-separator")
+            .long("phrase-separator")
+            .takes_value(true)
+            .requires("phrases")
+            .validator(|arg| {
+                if arg.chars().count() == 1 {
+                    Ok(())
+                } else {
+                    Err(String::from("Must be a single character"))
+                }
+            })
+            .help("Also treat this character as the space between words, eg \"_\" for lists that join phrases with underscores"),
+    ]
+}
+
+#[derive(Debug,Clone,Copy,PartialEq,Eq,Default)]
+pub struct PhraseOptions {
+    pub enabled: bool,
+    /// Converted to a space before the word is checked.
+    pub separator: Option<char>,
+}
+
+impl PhraseOptions {
+    pub fn from_args(args:&ArgMatches) -> PhraseOptions {
+        if args.is_present("phrases") && !cfg!(feature = "phrases") {
+            clap::Error::with_description(
+                "--phrases needs a binary built with the phrases feature, which adds the space to the alphabet",
+                clap::ErrorKind::ArgumentConflict,
+            ).exit();
+        }
+        PhraseOptions{
+            enabled: args.is_present("phrases"),
+            separator: args.value_of("phrase-separator").and_then(|s| s.chars().next()),
+        }
+    }
+
+    /// Converts separators in `word` to spaces, or gives the reason the word
+    /// can't be used.
+    pub fn check(&self, word:&str) -> Result<String, &'static str> {
+        let word:String = match self.separator {
+            Some(sep) => word.chars().map(|c| if c == sep { ' ' } else { c }).collect(),
+            None => word.to_string(),
+        };
+        if !word.contains(' ') {
+            return Ok(word);
+        }
+        if !self.enabled {
+            Err("containing spaces without --phrases")
+        } else if word.starts_with(' ') || word.ends_with(' ') {
+            Err("with leading or trailing spaces")
+        } else if word.contains("  ") {
+            Err("with double spaces")
+        } else {
+            Ok(word)
+        }
+    }
+}
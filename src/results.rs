@@ -0,0 +1,347 @@
+//! Output formats for found word squares.
+//!
+//! Besides the human-readable text and JSONL formats there is a compact binary
+//! format meant for archiving huge enumerations. A binary results file is a
+//! header followed by one fixed-size record per square:
+//!
+//! ```text
+//! magic     4 bytes  "RWSQ"
+//! version   u8       currently 1
+//! width     u8
+//! height    u8
+//! bits      u8       bits per packed code: 5 for the 32 letters, 6 for
+//!                    builds with the phrases feature's space
+//! alpha_len u16 LE   length in bytes of the alphabet string
+//! alphabet  UTF-8    one char per code, in code order
+//! records   width*height codes, row-major, packed MSB-first and padded to a byte
+//! ```
+//!
+//! The header carries everything needed to decode, so `decode-results` works on
+//! files produced by a binary of any size.
+//!
+//! When the wordlist had metadata columns, JSONL lines also get "row_meta" and
+//! "col_meta" arrays holding each word's columns. Binary records don't keep it.
+
+use std::io::{self, ErrorKind};
+use std::io::prelude::*;
+
+use serde_json::Value;
+
+use super::{alphabet, decode, invalid_data, WordMetadata, ALPHABET_SIZE};
+
+const MAGIC:&[u8; 4] = b"RWSQ";
+const VERSION:u8 = 1;
+
+#[derive(Debug,Clone,Copy,PartialEq,Eq)]
+pub enum OutputFormat {
+    Text,
+    Binary,
+    Jsonl,
+}
+
+impl OutputFormat {
+    pub fn from_name(name:&str) -> Option<OutputFormat> {
+        match name {
+            "text" => Some(OutputFormat::Text),
+            "binary" => Some(OutputFormat::Binary),
+            "jsonl" => Some(OutputFormat::Jsonl),
+            _ => None,
+        }
+    }
+
+    /// File extension for results written in this format.
+    pub fn extension(&self) -> &'static str {
+        match *self {
+            OutputFormat::Text => "txt",
+            OutputFormat::Binary => "bin",
+            OutputFormat::Jsonl => "jsonl",
+        }
+    }
+}
+
+#[derive(Debug,Clone,PartialEq,Eq)]
+pub struct ResultsHeader {
+    pub width: u8,
+    pub height: u8,
+    pub bits: u8,
+    pub alphabet: Vec<char>,
+}
+
+impl ResultsHeader {
+    /// The header describing `width` by `height` squares produced by this build.
+    pub fn new(width:usize, height:usize) -> ResultsHeader {
+        ResultsHeader{
+            width: width as u8,
+            height: height as u8,
+            bits: code_bits(ALPHABET_SIZE),
+            alphabet: alphabet(),
+        }
+    }
+
+    fn record_len(&self) -> usize {
+        let total_bits = (self.width as usize) * (self.height as usize) * (self.bits as usize);
+        total_bits.div_ceil(8)
+    }
+
+    pub fn write_to(&self, out:&mut impl Write) -> io::Result<()> {
+        let alphabet:String = self.alphabet.iter().collect();
+        out.write_all(MAGIC)?;
+        out.write_all(&[VERSION, self.width, self.height, self.bits])?;
+        out.write_all(&(alphabet.len() as u16).to_le_bytes())?;
+        out.write_all(alphabet.as_bytes())?;
+        Ok(())
+    }
+
+    pub fn read_from(input:&mut impl Read) -> io::Result<ResultsHeader> {
+        let mut fixed = [0u8; 10];
+        input.read_exact(&mut fixed)?;
+        if &fixed[0..4] != MAGIC {
+            return Err(invalid_data("not a binary results file (bad magic)"));
+        }
+        if fixed[4] != VERSION {
+            return Err(invalid_data(&format!("unsupported results file version {}", fixed[4])));
+        }
+        let (width, height, bits) = (fixed[5], fixed[6], fixed[7]);
+        if bits == 0 || bits > 8 {
+            return Err(invalid_data(&format!("invalid code size of {} bits", bits)));
+        }
+        let len = u16::from_le_bytes([fixed[8], fixed[9]]) as usize;
+        let mut alphabet_bytes = vec![0u8; len];
+        input.read_exact(&mut alphabet_bytes)?;
+        let alphabet = String::from_utf8(alphabet_bytes)
+            .map_err(|_| invalid_data("alphabet is not valid UTF-8"))?
+            .chars()
+            .collect();
+        Ok(ResultsHeader{width, height, bits, alphabet})
+    }
+
+    /// Turns a record's codes back into the words of each row.
+    pub fn rows(&self, codes:&[u8]) -> io::Result<Vec<String>> {
+        codes.chunks(self.width as usize).map(|row| {
+            row.iter().map(|&code| {
+                self.alphabet.get(code as usize).cloned()
+                    .ok_or_else(|| invalid_data(&format!("code {} is outside the alphabet", code)))
+            }).collect()
+        }).collect()
+    }
+}
+
+/// Number of bits needed to store any code below `alphabet_size`.
+fn code_bits(alphabet_size:u8) -> u8 {
+    (8 - (alphabet_size - 1).leading_zeros()) as u8
+}
+
+fn pack_codes(codes:&[u8], bits:u8, out:&mut Vec<u8>) {
+    let mut acc:u32 = 0;
+    let mut acc_bits:u8 = 0;
+    for &code in codes {
+        acc = (acc << bits) | (code as u32);
+        acc_bits += bits;
+        while acc_bits >= 8 {
+            acc_bits -= 8;
+            out.push((acc >> acc_bits) as u8);
+        }
+        acc &= (1 << acc_bits) - 1;
+    }
+    if acc_bits > 0 {
+        out.push((acc << (8 - acc_bits)) as u8);
+    }
+}
+
+fn unpack_codes(bytes:&[u8], bits:u8, count:usize) -> Vec<u8> {
+    let mut res = Vec::with_capacity(count);
+    let mut acc:u32 = 0;
+    let mut acc_bits:u8 = 0;
+    let mut bytes = bytes.iter();
+    while res.len() < count {
+        if acc_bits < bits {
+            acc = (acc << 8) | (*bytes.next().unwrap() as u32);
+            acc_bits += 8;
+        }
+        acc_bits -= bits;
+        res.push((acc >> acc_bits) as u8);
+        acc &= (1 << acc_bits) - 1;
+    }
+    res
+}
+
+fn write_rows(out:&mut impl Write, format:OutputFormat, rows:&[String]) -> io::Result<()> {
+    match format {
+        OutputFormat::Text => writeln!(out, "{}", rows.join("-")),
+        OutputFormat::Jsonl => {
+            let width = rows.first().map(|r| r.chars().count()).unwrap_or(0);
+            let row_chars:Vec<Vec<char>> = rows.iter().map(|r| r.chars().collect()).collect();
+            let cols:Vec<String> = (0..width).map(|i| {
+                row_chars.iter().map(|r| r[i]).collect()
+            }).collect();
+            writeln!(out, "{{\"rows\":{},\"cols\":{}}}", json!(rows), json!(cols))
+        },
+        OutputFormat::Binary => panic!("rows cannot be written in binary format"),
+    }
+}
+
+/// Writes squares found by `compute` in the chosen format.
+pub struct ResultWriter<'a, W:Write> {
+    out: W,
+    format: OutputFormat,
+    width: usize,
+    buf: Vec<u8>,
+    meta: Option<&'a WordMetadata>,
+}
+
+impl<'a, W:Write> ResultWriter<'a, W> {
+    pub fn new(mut out:W, format:OutputFormat, width:usize, height:usize) -> io::Result<ResultWriter<'a, W>> {
+        if format == OutputFormat::Binary {
+            ResultsHeader::new(width, height).write_to(&mut out)?;
+        }
+        Ok(ResultWriter{out, format, width, buf: Vec::new(), meta: None})
+    }
+
+    /// Adds each word's metadata to JSONL output.
+    pub fn with_meta(mut self, meta:&'a WordMetadata) -> ResultWriter<'a, W> {
+        if !meta.is_empty() {
+            self.meta = Some(meta);
+        }
+        self
+    }
+
+    pub fn write(&mut self, sq:&[u8]) -> io::Result<()> {
+        if self.format == OutputFormat::Binary {
+            self.buf.clear();
+            pack_codes(sq, code_bits(ALPHABET_SIZE), &mut self.buf);
+            return self.out.write_all(&self.buf);
+        }
+        let rows:Vec<String> = sq.chunks(self.width).map(|row| {
+            row.iter().map(|&code| decode(code).unwrap()).collect()
+        }).collect();
+        match self.meta {
+            Some(meta) if self.format == OutputFormat::Jsonl => {
+                let row_meta:Vec<Value> = sq.chunks(self.width).map(|row| meta.to_json(row)).collect();
+                let col_meta:Vec<Value> = (0..self.width).map(|i| {
+                    let col:Vec<u8> = sq.iter().skip(i).step_by(self.width).cloned().collect();
+                    meta.to_json(&col)
+                }).collect();
+                let cols:Vec<String> = (0..self.width).map(|i| {
+                    rows.iter().map(|r| r.chars().nth(i).unwrap()).collect()
+                }).collect();
+                writeln!(self.out, "{{\"rows\":{},\"cols\":{},\"row_meta\":{},\"col_meta\":{}}}",
+                    json!(rows), json!(cols), json!(row_meta), json!(col_meta))
+            },
+            _ => write_rows(&mut self.out, self.format, &rows),
+        }
+    }
+
+    pub fn flush(&mut self) -> io::Result<()> {
+        self.out.flush()
+    }
+
+    pub fn finish(mut self) -> io::Result<()> {
+        self.out.flush()
+    }
+}
+
+/// Converts a binary results file back to text or JSONL.
+pub fn decode_results(
+    mut input:impl Read,
+    out:&mut impl Write,
+    format:OutputFormat,
+) -> io::Result<u64> {
+    let header = ResultsHeader::read_from(&mut input)?;
+    let record_len = header.record_len();
+    let cells = (header.width as usize) * (header.height as usize);
+    let mut record = vec![0u8; record_len];
+    let mut count = 0u64;
+    loop {
+        let mut filled = 0;
+        while filled < record_len {
+            match input.read(&mut record[filled..]) {
+                Ok(0) => break,
+                Ok(n) => filled += n,
+                Err(ref e) if e.kind() == ErrorKind::Interrupted => (),
+                Err(e) => return Err(e),
+            }
+        }
+        if filled == 0 {
+            break;
+        }
+        if filled < record_len {
+            return Err(invalid_data("results file ends with a truncated record"));
+        }
+        let codes = unpack_codes(&record, header.bits, cells);
+        write_rows(out, format, &header.rows(&codes)?)?;
+        count += 1;
+    }
+    out.flush()?;
+    Ok(count)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// `count` codes spread over everything `bits` can hold.
+    fn codes(count:usize, bits:u8) -> Vec<u8> {
+        (0..count).map(|i| (i * 7 % (1 << bits)) as u8).collect()
+    }
+
+    /// A header for a 3x3 square, whose 9 codes don't fill whole bytes at 5 or 6 bits.
+    fn header(bits:u8) -> ResultsHeader {
+        let alphabet = (0..(1u32 << bits)).map(|i| std::char::from_u32('!' as u32 + i).unwrap()).collect();
+        ResultsHeader{width: 3, height: 3, bits, alphabet}
+    }
+
+    #[test]
+    fn pack_round_trip() {
+        for &bits in &[5, 6] {
+            let codes = codes(9, bits);
+            let mut packed = Vec::new();
+            pack_codes(&codes, bits, &mut packed);
+            assert_eq!(packed.len(), header(bits).record_len());
+            assert_eq!(unpack_codes(&packed, bits, codes.len()), codes);
+        }
+    }
+
+    #[test]
+    fn code_bits_fit_alphabet() {
+        assert_eq!(code_bits(32), 5);
+        assert_eq!(code_bits(33), 6);
+    }
+
+    #[test]
+    fn decode_round_trip() {
+        for &bits in &[5, 6] {
+            let header = header(bits);
+            let mut file = Vec::new();
+            header.write_to(&mut file).unwrap();
+            let squares = [codes(9, bits), codes(9, bits).into_iter().rev().collect()];
+            for sq in &squares {
+                pack_codes(sq, bits, &mut file);
+            }
+            let mut out = Vec::new();
+            assert_eq!(decode_results(&file[..], &mut out, OutputFormat::Text).unwrap(), 2);
+            let expected:String = squares.iter().map(|sq| {
+                format!("{}\n", header.rows(sq).unwrap().join("-"))
+            }).collect();
+            assert_eq!(String::from_utf8(out).unwrap(), expected);
+        }
+    }
+
+    #[test]
+    fn truncated_record() {
+        let header = header(5);
+        let mut file = Vec::new();
+        header.write_to(&mut file).unwrap();
+        pack_codes(&codes(9, 5), 5, &mut file);
+        file.pop();
+        let err = decode_results(&file[..], &mut Vec::new(), OutputFormat::Text).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn bad_magic() {
+        let mut file = Vec::new();
+        header(5).write_to(&mut file).unwrap();
+        file[0] = b'X';
+        assert!(ResultsHeader::read_from(&mut &file[..]).is_err());
+    }
+}
@@ -0,0 +1,32 @@
+//! A small seedable random number generator, for generated benchmark words
+//! and the random probes of `estimate`. Not suitable for anything secret.
+
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// xorshift64*.
+pub struct Rng(u64);
+
+impl Rng {
+    /// Seeds of 0 are replaced, as xorshift would only ever return 0.
+    pub fn new(seed:u64) -> Rng {
+        Rng(if seed == 0 { 0x9e37_79b9_7f4a_7c15 } else { seed })
+    }
+
+    /// Seeded from the clock, for when runs shouldn't repeat.
+    pub fn from_time() -> Rng {
+        let nanos = SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_nanos() as u64).unwrap_or(0);
+        Rng::new(nanos)
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        self.0 ^= self.0 >> 12;
+        self.0 ^= self.0 << 25;
+        self.0 ^= self.0 >> 27;
+        self.0.wrapping_mul(0x2545_f491_4f6c_dd1d)
+    }
+
+    /// A number below `n`, which must not be 0.
+    pub fn below(&mut self, n:u64) -> u64 {
+        self.next_u64() % n
+    }
+}
@@ -0,0 +1,272 @@
+//! Work-stealing scheduler for `compute`.
+//!
+//! A task is a partly filled square plus the index of the first empty cell;
+//! running it searches every completion of that prefix. Each worker keeps a
+//! deque of tasks and steals from the others when it runs dry. Work is split
+//! on demand: while some thread is idle and a worker has nothing queued, its
+//! search hands over the untried choices at its shallowest open cell as new
+//! tasks (see `Donate`), so big subtrees keep getting divided until the end.
+
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crossbeam_deque::{Injector, Steal, Stealer, Worker};
+
+use super::{compute, WordIndex};
+use super::search_stats::SearchStats;
+
+/// How many search steps `compute` takes between asking whether to donate.
+pub const DONATE_CHECK_INTERVAL:u32 = 1024;
+
+/// How many squares a worker collects before handing them on together.
+const RESULT_BATCH_SIZE:usize = 1024;
+
+/// How many times an idle worker yields before it starts sleeping between
+/// attempts to steal.
+const IDLE_SPINS:u32 = 64;
+const IDLE_SLEEP:Duration = Duration::from_micros(100);
+
+/// A prefix of a square with `S` cells and the index of its first unfilled cell.
+pub type Task<const S:usize> = ([u8; S], u8);
+
+/// Lets a running search give parts of its subtree away.
+pub trait Donate<const S:usize> {
+    /// Whether the search should split off work now. Checked every
+    /// `DONATE_CHECK_INTERVAL` steps, so it should be cheap.
+    fn wants_work(&self) -> bool;
+    fn donate(&mut self, task:Task<S>);
+}
+
+/// For searches that always run to completion on their own.
+pub struct NoDonate;
+
+impl<const S:usize> Donate<S> for NoDonate {
+    fn wants_work(&self) -> bool {
+        false
+    }
+
+    fn donate(&mut self, _task:Task<S>) {
+        unreachable!("NoDonate never wants work")
+    }
+}
+
+/// Every task one cell deeper than `task`.
+fn expand<const W:usize, const H:usize, const S:usize>(index:&WordIndex<W, H>, task:Task<S>, stats:&mut impl SearchStats) -> Vec<Task<S>> {
+    let mut tasks = Vec::new();
+    compute(index, task.0, task.1, task.1 + 1, |sq, idx| tasks.push((sq, idx)), &mut NoDonate, stats);
+    tasks
+}
+
+/// Splits the search from `root` into the tasks with the first `depth` cells
+/// filled, which must leave at least one cell empty. Also gives the number of
+/// nodes explored along the way, as `compute` counts them, and passes its
+/// steps to `stats`.
+pub fn split_at_depth<const W:usize, const H:usize, const S:usize>(
+    index:&WordIndex<W, H>,
+    root:Task<S>,
+    depth:u8,
+    stats:&mut impl SearchStats,
+) -> (Vec<Task<S>>, u64) {
+    let mut frontier = vec![root];
+    let mut nodes = 0;
+    while frontier.first().is_some_and(|t| t.1 < depth) {
+        frontier = frontier.into_iter().flat_map(|t| expand(index, t, stats)).collect();
+        nodes += frontier.len() as u64;
+    }
+    (frontier, nodes)
+}
+
+/// Splits the search from `root` one cell at a time until there are at least
+/// `min_tasks` tasks or only the last cell is left empty. Also gives the
+/// number of nodes explored, like `split_at_depth`.
+pub fn split_adaptive<const W:usize, const H:usize, const S:usize>(
+    index:&WordIndex<W, H>,
+    root:Task<S>,
+    min_tasks:usize,
+    stats:&mut impl SearchStats,
+) -> (Vec<Task<S>>, u64) {
+    let mut frontier = vec![root];
+    let mut nodes = 0;
+    while frontier.len() < min_tasks && frontier.first().is_some_and(|t| (t.1 as usize) + 1 < S) {
+        frontier = frontier.into_iter().flat_map(|t| expand(index, t, stats)).collect();
+        nodes += frontier.len() as u64;
+    }
+    (frontier, nodes)
+}
+
+/// State shared by all workers.
+struct Shared<const S:usize> {
+    injector: Injector<Task<S>>,
+    stealers: Vec<Stealer<Task<S>>>,
+    /// Tasks queued or running; the search is over when this reaches 0.
+    pending: AtomicUsize,
+    /// Workers currently looking for a task.
+    idle: AtomicUsize,
+}
+
+impl<const S:usize> Shared<S> {
+    fn steal(&self, me:usize) -> Option<Task<S>> {
+        loop {
+            let mut retry = false;
+            match self.injector.steal() {
+                Steal::Success(task) => return Some(task),
+                Steal::Retry => retry = true,
+                Steal::Empty => (),
+            }
+            for (i, stealer) in self.stealers.iter().enumerate() {
+                if i == me {
+                    continue;
+                }
+                match stealer.steal() {
+                    Steal::Success(task) => return Some(task),
+                    Steal::Retry => retry = true,
+                    Steal::Empty => (),
+                }
+            }
+            if !retry {
+                return None;
+            }
+        }
+    }
+}
+
+struct WorkerDonor<'a, const S:usize> {
+    local: &'a Worker<Task<S>>,
+    shared: &'a Shared<S>,
+    donated: u64,
+}
+
+impl<'a, const S:usize> Donate<S> for WorkerDonor<'a, S> {
+    fn wants_work(&self) -> bool {
+        self.local.is_empty() && self.shared.idle.load(Ordering::Relaxed) > 0
+    }
+
+    fn donate(&mut self, task:Task<S>) {
+        self.shared.pending.fetch_add(1, Ordering::SeqCst);
+        self.local.push(task);
+        self.donated += 1;
+    }
+}
+
+/// What one worker did, for the utilization report.
+#[derive(Debug,Default,Clone)]
+pub struct WorkerStats {
+    pub tasks: u64,
+    pub stolen: u64,
+    pub donated: u64,
+    /// Nodes explored by this worker's searches.
+    pub nodes: u64,
+    pub busy: Duration,
+}
+
+/// Searches every square starting from `roots` on `num_threads` threads.
+/// Each thread collects the squares it finds and passes them to `on_batch`
+/// in batches, at most `RESULT_BATCH_SIZE` at a time and at the end of every
+/// task, so results never wait long. Also gives the search steps of all
+/// threads counted with `St`.
+pub fn run<const W:usize, const H:usize, const S:usize, St, F>(
+    index:Arc<WordIndex<W, H>>,
+    roots:Vec<Task<S>>,
+    num_threads:usize,
+    on_batch:F,
+) -> (Vec<WorkerStats>, St)
+    where St:SearchStats + 'static, F:Fn(Vec<[u8; S]>) + Send + Sync + 'static
+{
+    let workers:Vec<Worker<Task<S>>> = (0..num_threads).map(|_| Worker::new_lifo()).collect();
+    let shared = Arc::new(Shared{
+        injector: Injector::new(),
+        stealers: workers.iter().map(|w| w.stealer()).collect(),
+        pending: AtomicUsize::new(roots.len()),
+        idle: AtomicUsize::new(0),
+    });
+    for task in roots {
+        shared.injector.push(task);
+    }
+    let on_batch = Arc::new(on_batch);
+
+    let handles:Vec<_> = workers.into_iter().enumerate().map(|(me, local)| {
+        let shared = Arc::clone(&shared);
+        let index = Arc::clone(&index);
+        let on_batch = Arc::clone(&on_batch);
+        thread::spawn(move || {
+            let mut stats = WorkerStats::default();
+            let mut search = St::default();
+            let mut batch = Vec::with_capacity(RESULT_BATCH_SIZE);
+            let mut donor = WorkerDonor{local: &local, shared: &shared, donated: 0};
+            loop {
+                let task = match donor.local.pop() {
+                    Some(task) => task,
+                    None => {
+                        shared.idle.fetch_add(1, Ordering::SeqCst);
+                        let mut attempts = 0u32;
+                        let found = loop {
+                            if let Some(task) = shared.steal(me) {
+                                break Some(task);
+                            }
+                            if shared.pending.load(Ordering::SeqCst) == 0 {
+                                break None;
+                            }
+                            attempts += 1;
+                            if attempts < IDLE_SPINS {
+                                thread::yield_now();
+                            } else {
+                                thread::sleep(IDLE_SLEEP);
+                            }
+                        };
+                        shared.idle.fetch_sub(1, Ordering::SeqCst);
+                        match found {
+                            Some(task) => {
+                                stats.stolen += 1;
+                                task
+                            },
+                            None => break,
+                        }
+                    },
+                };
+                let start = Instant::now();
+                stats.nodes += compute(&index, task.0, task.1, S as u8, |sq, _| {
+                    batch.push(sq);
+                    if batch.len() == RESULT_BATCH_SIZE {
+                        on_batch(std::mem::replace(&mut batch, Vec::with_capacity(RESULT_BATCH_SIZE)));
+                    }
+                }, &mut donor, &mut search);
+                if !batch.is_empty() {
+                    on_batch(std::mem::replace(&mut batch, Vec::with_capacity(RESULT_BATCH_SIZE)));
+                }
+                stats.busy += start.elapsed();
+                stats.tasks += 1;
+                shared.pending.fetch_sub(1, Ordering::SeqCst);
+            }
+            stats.donated = donor.donated;
+            (stats, search)
+        })
+    }).collect();
+
+    let mut search = St::default();
+    let stats = handles.into_iter().map(|h| {
+        let (stats, worker_search) = h.join().unwrap();
+        search.merge(&worker_search);
+        stats
+    }).collect();
+    (stats, search)
+}
+
+/// Prints how busy each worker was over `elapsed` to STDERR.
+pub fn report(stats:&[WorkerStats], elapsed:Duration) {
+    let wall = elapsed.as_secs_f64().max(1e-9);
+    let mut total_busy = 0.0;
+    for (i, s) in stats.iter().enumerate() {
+        let busy = s.busy.as_secs_f64();
+        total_busy += busy;
+        eprintln!(
+            "Thread {}: {} tasks ({} stolen, {} given away), busy {:.1}%",
+            i, s.tasks, s.stolen, s.donated, 100.0 * busy / wall,
+        );
+    }
+    eprintln!(
+        "Utilization {:.1}% over {:.2}s.",
+        100.0 * total_busy / (wall * stats.len().max(1) as f64), wall,
+    );
+}
@@ -0,0 +1,113 @@
+//! Counting what `compute` does at each cell, for `compute --search-stats`.
+//!
+//! `compute` is generic over `SearchStats`, so searches run with `NoStats`
+//! compile down to the uncounted loop and the hot path stays as fast as before.
+
+use std::io;
+use std::io::prelude::*;
+
+use super::summary::{self, SummaryFormat};
+
+/// Gets told about each step of a search. Cells are given by index, in the
+/// order they are filled.
+pub trait SearchStats: Default + Send {
+    /// A cell was filled with a letter the index allows.
+    fn node(&mut self, cell:u8);
+    /// A cell was reached with no letter allowed by both its row and column.
+    fn dead_end(&mut self, cell:u8);
+    /// Every letter of a cell has been tried, so the search goes back a cell.
+    fn backtrack(&mut self, cell:u8);
+    /// A square was completed by filling `cell`, the last one.
+    fn solution(&mut self, cell:u8);
+    fn merge(&mut self, other:&Self);
+}
+
+/// Counts nothing.
+#[derive(Debug,Default,Clone,Copy)]
+pub struct NoStats;
+
+impl SearchStats for NoStats {
+    #[inline(always)]
+    fn node(&mut self, _cell:u8) {}
+    #[inline(always)]
+    fn dead_end(&mut self, _cell:u8) {}
+    #[inline(always)]
+    fn backtrack(&mut self, _cell:u8) {}
+    #[inline(always)]
+    fn solution(&mut self, _cell:u8) {}
+    fn merge(&mut self, _other:&Self) {}
+}
+
+#[derive(Debug,Default,Clone,Copy)]
+struct CellStats {
+    nodes: u64,
+    dead_ends: u64,
+    backtracks: u64,
+    solutions: u64,
+}
+
+/// Counts for each of the `S` cells of a square.
+#[derive(Debug,Clone)]
+pub struct DepthStats<const S:usize> {
+    cells: [CellStats; S],
+}
+
+impl<const S:usize> Default for DepthStats<S> {
+    fn default() -> Self {
+        DepthStats{cells: [CellStats::default(); S]}
+    }
+}
+
+impl<const S:usize> SearchStats for DepthStats<S> {
+    fn node(&mut self, cell:u8) {
+        self.cells[cell as usize].nodes += 1;
+    }
+
+    fn dead_end(&mut self, cell:u8) {
+        self.cells[cell as usize].dead_ends += 1;
+    }
+
+    fn backtrack(&mut self, cell:u8) {
+        self.cells[cell as usize].backtracks += 1;
+    }
+
+    fn solution(&mut self, cell:u8) {
+        self.cells[cell as usize].solutions += 1;
+    }
+
+    fn merge(&mut self, other:&Self) {
+        for (mine, theirs) in self.cells.iter_mut().zip(other.cells.iter()) {
+            mine.nodes += theirs.nodes;
+            mine.dead_ends += theirs.dead_ends;
+            mine.backtracks += theirs.backtracks;
+            mine.solutions += theirs.solutions;
+        }
+    }
+}
+
+const COLUMNS:&[&str] = &["depth", "row", "col", "nodes", "dead_ends", "backtracks", "solutions", "branching"];
+
+impl<const S:usize> DepthStats<S> {
+    /// Writes a markdown table with a line per cell of a grid `width` wide.
+    /// Depth counts the cells filled, so the cell at index 0 is depth 1.
+    /// Branching is the average number of letters tried per node at the
+    /// previous depth that reached this cell.
+    pub fn write_table(&self, out:&mut impl Write, width:usize) -> io::Result<()> {
+        let mut parents = 1u64;
+        let rows:Vec<_> = self.cells.iter().enumerate().map(|(i, c)| {
+            let branching = c.nodes as f64 / parents.max(1) as f64;
+            parents = c.nodes - c.solutions;
+            vec![
+                json!(i + 1),
+                json!(i / width),
+                json!(i % width),
+                json!(c.nodes),
+                json!(c.dead_ends),
+                json!(c.backtracks),
+                json!(c.solutions),
+                json!(branching),
+            ]
+        }).collect();
+        summary::write_table(out, SummaryFormat::Markdown, COLUMNS, &rows)
+    }
+}
@@ -0,0 +1,88 @@
+//! Counters for the `wordlist-preprocess --stats` report.
+
+use std::collections::BTreeMap;
+use std::io;
+use std::io::prelude::*;
+
+use fnv::FnvHashMap;
+use serde_json::Value;
+
+/// How many of the most frequent unencodable characters to list.
+const TOP_UNENCODABLE:usize = 20;
+
+#[derive(Debug,Default)]
+pub struct PreprocessStats {
+    read: u64,
+    kept: u64,
+    dropped: BTreeMap<&'static str,u64>,
+    lengths: BTreeMap<usize,u64>,
+    unencodable: FnvHashMap<char,u64>,
+}
+
+impl PreprocessStats {
+    pub fn keep(&mut self, word:&str) {
+        self.read += 1;
+        self.kept += 1;
+        *self.lengths.entry(word.chars().count()).or_insert(0) += 1;
+    }
+
+    pub fn reject(&mut self, reason:&'static str) {
+        self.read += 1;
+        *self.dropped.entry(reason).or_insert(0) += 1;
+    }
+
+    pub fn unencodable(&mut self, chars:&[char]) {
+        for &c in chars {
+            *self.unencodable.entry(c).or_insert(0) += 1;
+        }
+    }
+
+    fn top_unencodable(&self) -> Vec<(char,u64)> {
+        let mut counts:Vec<(char,u64)> = self.unencodable.iter().map(|(&c, &n)| (c, n)).collect();
+        counts.sort_unstable_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(&b.0)));
+        counts.truncate(TOP_UNENCODABLE);
+        counts
+    }
+
+    pub fn write_table(&self, out:&mut impl Write) -> io::Result<()> {
+        writeln!(out, "Words read:    {:>10}", self.read)?;
+        writeln!(out, "Words kept:    {:>10}", self.kept)?;
+        writeln!(out, "Words dropped: {:>10}", self.read - self.kept)?;
+        for (reason, count) in &self.dropped {
+            writeln!(out, "  {:<36} {:>10}", reason, count)?;
+        }
+        writeln!(out)?;
+        writeln!(out, "Kept words by length (usable lengths are 2 to 15):")?;
+        for (len, count) in &self.lengths {
+            writeln!(out, "  {:>3} {:>10}", len, count)?;
+        }
+        let top = self.top_unencodable();
+        if !top.is_empty() {
+            writeln!(out)?;
+            writeln!(out, "Most frequent unencodable characters:")?;
+            for (c, count) in top {
+                writeln!(out, "  U+{:04X} {:<10} {:>10}", c as u32, format!("{:?}", c), count)?;
+            }
+        }
+        Ok(())
+    }
+
+    pub fn to_json(&self) -> Value {
+        let lengths:Vec<Value> = self.lengths.iter().map(|(len, count)| json!({
+            "length": len,
+            "count": count,
+        })).collect();
+        let top:Vec<Value> = self.top_unencodable().into_iter().map(|(c, count)| json!({
+            "char": c.to_string(),
+            "codepoint": format!("U+{:04X}", c as u32),
+            "count": count,
+        })).collect();
+        json!({
+            "read": self.read,
+            "kept": self.kept,
+            "dropped": self.dropped,
+            "lengths": lengths,
+            "unencodable": top,
+        })
+    }
+}
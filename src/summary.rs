@@ -0,0 +1,153 @@
+//! Per-size summary of a compute run or `--sizes` sweep: how many words each
+//! size could use, what was found, and how long it took.
+
+use std::io;
+use std::io::prelude::*;
+use std::time::Duration;
+
+use clap::{Arg, ArgMatches};
+use serde_json::{Map, Value};
+
+/// Arguments read by `SummaryFormat::from_args` and `compute`.
+pub fn summary_args<'a, 'b>() -> Vec<Arg<'a, 'b>> {
+    vec![
+        Arg::with_name("summary")
+            .long("summary")
+            .takes_value(true)
+            .help("Write a table of each size's word counts, squares found, nodes explored and wall time to this path. With --sizes it defaults to summary.md (or .csv/.json) in the output directory."),
+        Arg::with_name("summary-format")
+            .long("summary-format")
+            .takes_value(true)
+            .possible_values(&["markdown", "csv", "json"])
+            .default_value("markdown")
+            .help("Format of the --summary table"),
+    ]
+}
+
+#[derive(Debug,Clone,Copy,PartialEq,Eq)]
+pub enum SummaryFormat {
+    Markdown,
+    Csv,
+    Json,
+}
+
+impl SummaryFormat {
+    pub fn from_name(name:&str) -> Option<SummaryFormat> {
+        match name {
+            "markdown" => Some(SummaryFormat::Markdown),
+            "csv" => Some(SummaryFormat::Csv),
+            "json" => Some(SummaryFormat::Json),
+            _ => None,
+        }
+    }
+
+    pub fn from_args(args:&ArgMatches) -> SummaryFormat {
+        SummaryFormat::from_name(args.value_of("summary-format").unwrap()).unwrap()
+    }
+
+    /// File extension for summaries written in this format.
+    pub fn extension(&self) -> &'static str {
+        match *self {
+            SummaryFormat::Markdown => "md",
+            SummaryFormat::Csv => "csv",
+            SummaryFormat::Json => "json",
+        }
+    }
+}
+
+/// What searching one size found.
+#[derive(Debug,Clone,Default)]
+pub struct SizeSummary {
+    pub width: usize,
+    pub height: usize,
+    pub row_words: u32,
+    pub col_words: u32,
+    pub squares: u64,
+    pub nodes: u64,
+    /// Time spent indexing and searching this size.
+    pub elapsed: Duration,
+}
+
+const COLUMNS:&[&str] = &["width", "height", "row_words", "col_words", "squares", "nodes", "seconds"];
+
+impl SizeSummary {
+    fn row(&self) -> Vec<Value> {
+        vec![
+            json!(self.width),
+            json!(self.height),
+            json!(self.row_words),
+            json!(self.col_words),
+            json!(self.squares),
+            json!(self.nodes),
+            json!(self.elapsed.as_secs_f64()),
+        ]
+    }
+}
+
+pub fn write_summary(out:&mut impl Write, format:SummaryFormat, sizes:&[SizeSummary]) -> io::Result<()> {
+    let rows:Vec<Vec<Value>> = sizes.iter().map(|s| s.row()).collect();
+    write_table(out, format, COLUMNS, &rows)
+}
+
+/// Shows a number with fractions to the millisecond, or without any if it's
+/// a whole number such as a rounded count, or in scientific notation if it's
+/// too big for either to be readable. Anything else is shown as is.
+fn cell_text(value:&Value) -> String {
+    match *value {
+        Value::Number(ref n) if n.is_f64() => {
+            let n = n.as_f64().unwrap();
+            if n.abs() >= 1e15 {
+                format!("{:.3e}", n)
+            } else if n.fract() == 0.0 {
+                format!("{:.0}", n)
+            } else {
+                format!("{:.3}", n)
+            }
+        },
+        Value::String(ref s) => s.clone(),
+        ref v => v.to_string(),
+    }
+}
+
+/// Writes a table with the named columns. Markdown headings have underscores
+/// shown as spaces; JSON is an array with an object per row.
+pub fn write_table(out:&mut impl Write, format:SummaryFormat, columns:&[&str], rows:&[Vec<Value>]) -> io::Result<()> {
+    match format {
+        SummaryFormat::Markdown => {
+            let headings:Vec<String> = columns.iter().map(|c| c.replace('_', " ")).collect();
+            let cells:Vec<Vec<String>> = rows.iter().map(|r| r.iter().map(cell_text).collect()).collect();
+            let widths:Vec<usize> = (0..columns.len()).map(|i| {
+                cells.iter().map(|r| r[i].len()).chain(Some(headings[i].len())).max().unwrap()
+            }).collect();
+            let headings:Vec<String> = headings.iter().zip(&widths).map(|(h, w)| format!("{:<w$}", h, w = w)).collect();
+            writeln!(out, "| {} |", headings.join(" | "))?;
+            // Numbers are right-aligned, everything else left-aligned.
+            let numeric:Vec<bool> = (0..columns.len()).map(|i| rows.iter().all(|r| r[i].is_number())).collect();
+            let rules:Vec<String> = widths.iter().zip(&numeric).map(|(w, &num)| {
+                if num { format!("{}:", "-".repeat(w - 1)) } else { "-".repeat(*w) }
+            }).collect();
+            writeln!(out, "| {} |", rules.join(" | "))?;
+            for row in &cells {
+                let row:Vec<String> = row.iter().zip(&widths).zip(&numeric).map(|((cell, w), &num)| {
+                    if num { format!("{:>w$}", cell, w = w) } else { format!("{:<w$}", cell, w = w) }
+                }).collect();
+                writeln!(out, "| {} |", row.join(" | "))?;
+            }
+        },
+        SummaryFormat::Csv => {
+            writeln!(out, "{}", columns.join(","))?;
+            for row in rows {
+                let row:Vec<String> = row.iter().map(cell_text).collect();
+                writeln!(out, "{}", row.join(","))?;
+            }
+        },
+        SummaryFormat::Json => {
+            let rows:Vec<Value> = rows.iter().map(|row| {
+                let obj:Map<String, Value> = columns.iter().map(|c| c.to_string()).zip(row.iter().cloned()).collect();
+                Value::Object(obj)
+            }).collect();
+            writeln!(out, "{}", serde_json::to_string_pretty(&rows).unwrap())?;
+        },
+    }
+    out.flush()
+}
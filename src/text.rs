@@ -0,0 +1,126 @@
+//! Unicode normalization and case folding applied to words before they are
+//! encoded, shared by wordlist-preprocess, build-index and compute so a word
+//! is treated the same way everywhere.
+
+use clap::{Arg, ArgMatches};
+use unicode_normalization::UnicodeNormalization;
+use unicode_normalization::char::is_combining_mark;
+
+#[derive(Debug,Clone,Copy,PartialEq,Eq)]
+pub enum NormalizationForm {
+    None,
+    Nfc,
+    Nfd,
+    Nfkc,
+    Nfkd,
+}
+
+#[derive(Debug,Clone,Copy,PartialEq,Eq)]
+pub enum Accents {
+    Keep,
+    Strip,
+}
+
+#[derive(Debug,Clone,Copy,PartialEq,Eq)]
+pub struct TextOptions {
+    pub normalization: NormalizationForm,
+    pub case_fold: bool,
+    pub accents: Accents,
+}
+
+impl Default for TextOptions {
+    fn default() -> Self {
+        TextOptions{
+            normalization: NormalizationForm::None,
+            case_fold: false,
+            accents: Accents::Keep,
+        }
+    }
+}
+
+/// Arguments read by `TextOptions::from_args`.
+pub fn text_args<'a, 'b>() -> Vec<Arg<'a, 'b>> {
+    vec![
+        Arg::with_name("normalize")
+            .long("normalize")
+            .takes_value(true)
+            .possible_values(&["none", "nfc", "nfd", "nfkc", "nfkd"])
+            .default_value("none")
+            .help("Unicode normalization form to convert words to before encoding"),
+        Arg::with_name("case-fold")
+            .long("case-fold")
+            .help("Apply full Unicode case folding, so upper-case words are kept as lower-case"),
+        Arg::with_name("accents")
+            .long("accents")
+            .takes_value(true)
+            .possible_values(&["keep", "strip"])
+            .default_value("keep")
+            .help("Whether to keep accents, or strip them so eg 'é' becomes 'e'"),
+    ]
+}
+
+impl TextOptions {
+    pub fn from_args(args:&ArgMatches) -> TextOptions {
+        TextOptions{
+            normalization: match args.value_of("normalize").unwrap() {
+                "nfc" => NormalizationForm::Nfc,
+                "nfd" => NormalizationForm::Nfd,
+                "nfkc" => NormalizationForm::Nfkc,
+                "nfkd" => NormalizationForm::Nfkd,
+                _ => NormalizationForm::None,
+            },
+            case_fold: args.is_present("case-fold"),
+            accents: match args.value_of("accents").unwrap() {
+                "strip" => Accents::Strip,
+                _ => Accents::Keep,
+            },
+        }
+    }
+
+    pub fn apply(&self, word:&str) -> String {
+        if *self == TextOptions::default() {
+            return word.to_string();
+        }
+        let mut res = if self.case_fold { case_fold(word) } else { word.to_string() };
+        if self.accents == Accents::Strip {
+            res = res.nfd().filter(|&c| !is_combining_mark(c)).nfc().collect();
+        }
+        match self.normalization {
+            NormalizationForm::None => res,
+            NormalizationForm::Nfc => res.nfc().collect(),
+            NormalizationForm::Nfd => res.nfd().collect(),
+            NormalizationForm::Nfkc => res.nfkc().collect(),
+            NormalizationForm::Nfkd => res.nfkd().collect(),
+        }
+    }
+}
+
+/// Full case folding (Unicode CaseFolding.txt statuses C and F).
+fn case_fold(word:&str) -> String {
+    caseless::default_case_fold_str(word)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::case_fold;
+
+    #[test]
+    fn simple() {
+        assert_eq!(case_fold("Word"), "word");
+    }
+
+    #[test]
+    fn expands() {
+        assert_eq!(case_fold("Straße"), "strasse");
+        assert_eq!(case_fold("\u{1f0}"), "j\u{30c}");
+        assert_eq!(case_fold("\u{390}"), "\u{3b9}\u{308}\u{301}");
+        assert_eq!(case_fold("\u{1f88}"), "\u{1f00}\u{3b9}");
+        assert_eq!(case_fold("ﬃ"), "ffi");
+    }
+
+    #[test]
+    fn folds_to_upper_case() {
+        // Cherokee folds its later-encoded lower-case letters to upper case.
+        assert_eq!(case_fold("\u{ab70}"), "\u{13a0}");
+    }
+}
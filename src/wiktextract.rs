@@ -0,0 +1,72 @@
+//! Reading words out of wiktextract's JSONL dumps, where each line is one
+//! entry (a word in one language with one part of speech) with its senses.
+
+use std::io;
+
+use serde_json::Value;
+
+use super::invalid_data;
+
+/// Pseudo-tag matching entries whose part of speech marks them as proper nouns,
+/// which wiktextract records as a part of speech rather than a tag.
+const PROPER_NOUN_TAG:&str = "proper noun";
+
+fn tags(value:&Value) -> Vec<&str> {
+    match value.get("tags").and_then(Value::as_array) {
+        Some(tags) => tags.iter().filter_map(Value::as_str).collect(),
+        None => Vec::new(),
+    }
+}
+
+#[derive(Debug,Clone,Default)]
+pub struct EntryFilter {
+    /// Only keep entries with this language code, eg "en".
+    pub lang: Option<String>,
+    /// Only keep entries with one of these parts of speech; empty keeps all.
+    pub pos: Vec<String>,
+    /// Drop entries tagged with any of these, or whose senses all are.
+    pub exclude_tags: Vec<String>,
+}
+
+impl EntryFilter {
+    fn excluded(&self, tags:&[&str]) -> bool {
+        tags.iter().any(|tag| self.exclude_tags.iter().any(|ex| ex == tag))
+    }
+
+    /// The entry's word, if the entry passes the filter.
+    pub fn word(&self, entry:&Value) -> Option<String> {
+        let word = entry.get("word")?.as_str()?;
+        if let Some(ref lang) = self.lang {
+            if entry.get("lang_code").and_then(Value::as_str) != Some(lang.as_str()) {
+                return None;
+            }
+        }
+        let pos = entry.get("pos").and_then(Value::as_str).unwrap_or("");
+        if !self.pos.is_empty() && !self.pos.iter().any(|p| p == pos) {
+            return None;
+        }
+        let is_proper_noun = pos == "name" || pos == PROPER_NOUN_TAG;
+        if is_proper_noun && self.excluded(&[PROPER_NOUN_TAG]) {
+            return None;
+        }
+        if self.excluded(&tags(entry)) {
+            return None;
+        }
+        if let Some(senses) = entry.get("senses").and_then(Value::as_array) {
+            if !senses.is_empty() && senses.iter().all(|sense| self.excluded(&tags(sense))) {
+                return None;
+            }
+        }
+        Some(word.to_string())
+    }
+
+    /// Parses one JSONL line, giving the word if it passes the filter.
+    pub fn line_word(&self, line:&str) -> io::Result<Option<String>> {
+        if line.trim().is_empty() {
+            return Ok(None);
+        }
+        let entry:Value = serde_json::from_str(line)
+            .map_err(|e| invalid_data(&format!("invalid wiktextract entry: {}", e)))?;
+        Ok(self.word(&entry))
+    }
+}
@@ -0,0 +1,69 @@
+//! Where wordlist-preprocess writes its words: a single file (or stdout), or
+//! a directory with one file per word length.
+
+use std::collections::BTreeMap;
+use std::collections::btree_map::Entry;
+use std::fs::{self, File};
+use std::io::{self, BufWriter};
+use std::io::prelude::*;
+use std::path::PathBuf;
+
+pub enum WordlistWriter {
+    Single(BufWriter<Box<dyn Write>>),
+    ByLength {
+        dir: PathBuf,
+        files: BTreeMap<usize,BufWriter<File>>,
+    },
+}
+
+impl WordlistWriter {
+    /// Writes to `path`, or stdout for "-".
+    pub fn single(path:&str) -> io::Result<WordlistWriter> {
+        let out:Box<dyn Write> = if path == "-" {
+            Box::new(io::stdout())
+        } else {
+            Box::new(File::create(path)?)
+        };
+        Ok(WordlistWriter::Single(BufWriter::new(out)))
+    }
+
+    /// Writes words of length N to `words-N.txt` inside `dir`, creating it if needed.
+    pub fn by_length(dir:&str) -> io::Result<WordlistWriter> {
+        fs::create_dir_all(dir)?;
+        Ok(WordlistWriter::ByLength{ dir: PathBuf::from(dir), files: BTreeMap::new() })
+    }
+
+    pub fn write(&mut self, word:&str) -> io::Result<()> {
+        self.write_line(word, word)
+    }
+
+    /// Writes `line`, which holds `word` and possibly other columns.
+    pub fn write_line(&mut self, word:&str, line:&str) -> io::Result<()> {
+        match *self {
+            WordlistWriter::Single(ref mut out) => writeln!(out, "{}", line),
+            WordlistWriter::ByLength{ref dir, ref mut files} => {
+                let len = word.chars().count();
+                let out = match files.entry(len) {
+                    Entry::Occupied(e) => e.into_mut(),
+                    Entry::Vacant(e) => {
+                        let file = File::create(dir.join(format!("words-{}.txt", len)))?;
+                        e.insert(BufWriter::new(file))
+                    },
+                };
+                writeln!(out, "{}", line)
+            },
+        }
+    }
+
+    pub fn finish(self) -> io::Result<()> {
+        match self {
+            WordlistWriter::Single(mut out) => out.flush(),
+            WordlistWriter::ByLength{files, ..} => {
+                for (_, mut out) in files {
+                    out.flush()?;
+                }
+                Ok(())
+            },
+        }
+    }
+}